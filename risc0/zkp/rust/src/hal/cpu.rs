@@ -36,7 +36,6 @@ use crate::{
         sha_cpu,
     },
     field::Elem,
-    FRI_FOLD, INV_RATE,
 };
 
 pub struct CpuHal<'a, C: PolyFp> {
@@ -359,7 +358,9 @@ impl<'a, E: PolyFp> Hal for CpuHal<'a, E> {
     fn fri_fold(&self, output: &CpuBuffer<Fp>, input: &CpuBuffer<Fp>, mix: &Fp4) {
         let count = output.size() / EXT_SIZE;
         assert_eq!(output.size(), count * EXT_SIZE);
-        assert_eq!(input.size(), output.size() * FRI_FOLD);
+        let fri_fold = input.size() / output.size();
+        assert!(fri_fold.is_power_of_two());
+        assert_eq!(input.size(), output.size() * fri_fold);
         let mut output = output.as_slice_mut();
         let input = input.as_slice();
 
@@ -367,14 +368,14 @@ impl<'a, E: PolyFp> Hal for CpuHal<'a, E> {
         for idx in 0..count {
             let mut tot = Fp4::ZERO;
             let mut cur_mix = Fp4::from_u32(1);
-            for i in 0..FRI_FOLD {
-                let rev_i = bit_rev_32(i as u32) >> (32 - log2_ceil(FRI_FOLD));
+            for i in 0..fri_fold {
+                let rev_i = bit_rev_32(i as u32) >> (32 - log2_ceil(fri_fold));
                 let rev_idx = rev_i as usize * count + idx;
                 let factor = Fp4::new(
-                    input[0 * count * FRI_FOLD + rev_idx],
-                    input[1 * count * FRI_FOLD + rev_idx],
-                    input[2 * count * FRI_FOLD + rev_idx],
-                    input[3 * count * FRI_FOLD + rev_idx],
+                    input[0 * count * fri_fold + rev_idx],
+                    input[1 * count * fri_fold + rev_idx],
+                    input[2 * count * fri_fold + rev_idx],
+                    input[3 * count * fri_fold + rev_idx],
                 );
                 tot += cur_mix * factor;
                 cur_mix *= *mix;
@@ -428,9 +429,11 @@ impl<'a, E: PolyFp> Hal for CpuHal<'a, E> {
         po2: usize,
         steps: usize,
     ) {
-        const EXP_PO2: usize = log2_ceil(INV_RATE);
-
-        let domain = steps * INV_RATE;
+        // The check polynomial is evaluated over the whole expanded domain.
+        let domain = check.size() / EXT_SIZE;
+        let inv_rate = domain / steps;
+        assert_eq!(domain, steps * inv_rate);
+        let exp_po2 = log2_ceil(inv_rate);
         let code = code.as_slice();
         let data = data.as_slice();
         let accum = accum.as_slice();
@@ -445,10 +448,11 @@ impl<'a, E: PolyFp> Hal for CpuHal<'a, E> {
                     size: domain,
                     cycle,
                     mix: poly_mix,
+                    inv_rate,
                 },
                 args,
             );
-            let x = Fp::new(ROU_FWD[po2 + EXP_PO2]).pow(cycle);
+            let x = Fp::new(ROU_FWD[po2 + exp_po2]).pow(cycle);
             // TODO: what is this magic number 3?
             let y = (Fp::new(3) * x).pow(1 << po2);
             let ret = cond.tot * (y - Fp::new(1)).inv();
@@ -22,6 +22,7 @@ pub mod core;
 #[cfg(feature = "hal")]
 pub mod hal;
 mod merkle;
+pub mod params;
 #[cfg(feature = "prove")]
 pub mod prove;
 pub mod taps;
@@ -31,6 +32,7 @@ pub mod verify;
 pub mod field;
 
 pub const MIN_CYCLES: usize = 512;
+pub const MIN_CYCLES_PO2: usize = core::log2_ceil(MIN_CYCLES);
 pub const MAX_CYCLES_PO2: usize = 20;
 pub const MAX_CYCLES: usize = 1 << MAX_CYCLES_PO2;
 
@@ -41,7 +43,4 @@ pub const MIN_PO2: usize = core::log2_ceil(1 + ZK_CYCLES);
 
 pub const INV_RATE: usize = 4;
 const FRI_FOLD_PO2: usize = 4;
-const FRI_FOLD: usize = 1 << FRI_FOLD_PO2;
 const FRI_MIN_DEGREE: usize = 256;
-
-const CHECK_SIZE: usize = INV_RATE * core::fp4::EXT_SIZE;
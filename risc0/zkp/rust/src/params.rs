@@ -0,0 +1,164 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    core::{fp4::EXT_SIZE, log2_ceil, rou::MAX_ROU_PO2},
+    FRI_FOLD_PO2, FRI_MIN_DEGREE, INV_RATE, MAX_CYCLES_PO2, QUERIES,
+};
+
+/// The smallest supported `inv_rate`. The check polynomial of the circuit has
+/// a degree of up to this many times the trace size, so it must fit in the
+/// evaluation domain.
+pub const MIN_INV_RATE: usize = INV_RATE;
+
+/// The largest supported `inv_rate`, such that the evaluation domain of the
+/// largest trace still has a root of unity.
+pub const MAX_INV_RATE: usize = 1 << (MAX_ROU_PO2 - MAX_CYCLES_PO2);
+
+/// The number of seal words taken by [ProofParams].
+pub const PROOF_PARAMS_WORDS: usize = 4;
+
+/// The parameters of the proof system, chosen by the prover and recorded in
+/// the seal so that verifiers can enforce a minimum security level.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProofParams {
+    /// The number of FRI queries. This may not exceed
+    /// [ZK_CYCLES](crate::ZK_CYCLES), the number of randomized cycles padding
+    /// the trace, or the proof would no longer be zero-knowledge.
+    pub queries: usize,
+    /// The ratio of the size of the evaluation domain to the trace size. Must
+    /// be a power of 2 between [MIN_INV_RATE] and [MAX_INV_RATE].
+    pub inv_rate: usize,
+    /// The log2 of the number of evaluations folded together in each FRI
+    /// round.
+    pub fri_fold_po2: usize,
+    /// FRI folding stops once the degree of the polynomial is no more than
+    /// this.
+    pub fri_min_degree: usize,
+}
+
+impl ProofParams {
+    /// The parameters used when none are given: ~100 bits of conjectured
+    /// security.
+    pub const DEFAULT: ProofParams = ProofParams {
+        queries: QUERIES,
+        inv_rate: INV_RATE,
+        fri_fold_po2: FRI_FOLD_PO2,
+        fri_min_degree: FRI_MIN_DEGREE,
+    };
+
+    pub fn with_queries(self, queries: usize) -> Self {
+        Self { queries, ..self }
+    }
+
+    pub fn with_inv_rate(self, inv_rate: usize) -> Self {
+        Self { inv_rate, ..self }
+    }
+
+    pub fn with_fri_fold_po2(self, fri_fold_po2: usize) -> Self {
+        Self {
+            fri_fold_po2,
+            ..self
+        }
+    }
+
+    pub fn with_fri_min_degree(self, fri_min_degree: usize) -> Self {
+        Self {
+            fri_min_degree,
+            ..self
+        }
+    }
+
+    /// The number of evaluations folded together in each FRI round.
+    pub const fn fri_fold(&self) -> usize {
+        1 << self.fri_fold_po2
+    }
+
+    /// The number of columns of the check polynomial group.
+    pub const fn check_size(&self) -> usize {
+        self.inv_rate * EXT_SIZE
+    }
+
+    /// Returns the conjectured security of these parameters, in bits: each
+    /// query contributes the log2 of `inv_rate`.
+    pub const fn security_bits(&self) -> usize {
+        // The parameters may come from an untrusted seal.
+        self.queries.saturating_mul(log2_ceil(self.inv_rate))
+    }
+
+    /// Returns true if a proof can be produced and verified with these
+    /// parameters.
+    pub fn is_valid(&self) -> bool {
+        self.queries > 0
+            && self.inv_rate.is_power_of_two()
+            && (MIN_INV_RATE..=MAX_INV_RATE).contains(&self.inv_rate)
+            && (1..=MAX_CYCLES_PO2).contains(&self.fri_fold_po2)
+            && self.fri_min_degree >= self.fri_fold()
+    }
+
+    /// Encodes the parameters as they appear in the seal.
+    pub fn to_words(&self) -> [u32; PROOF_PARAMS_WORDS] {
+        [
+            self.queries as u32,
+            self.inv_rate as u32,
+            self.fri_fold_po2 as u32,
+            self.fri_min_degree as u32,
+        ]
+    }
+
+    /// Decodes the parameters as they appear in the seal. The result may not
+    /// be valid.
+    pub fn from_words(words: &[u32; PROOF_PARAMS_WORDS]) -> Self {
+        ProofParams {
+            queries: words[0] as usize,
+            inv_rate: words[1] as usize,
+            fri_fold_po2: words[2] as usize,
+            fri_min_degree: words[3] as usize,
+        }
+    }
+}
+
+impl Default for ProofParams {
+    fn default() -> Self {
+        ProofParams::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_params() {
+        let params = ProofParams::default();
+        assert!(params.is_valid());
+        assert_eq!(params.security_bits(), 100);
+        assert_eq!(params.fri_fold(), 16);
+        assert_eq!(params.check_size(), 16);
+        assert_eq!(ProofParams::from_words(&params.to_words()), params);
+    }
+
+    #[test]
+    fn invalid_params() {
+        let params = ProofParams::default();
+        assert!(!params.with_queries(0).is_valid());
+        assert!(!params.with_inv_rate(2).is_valid());
+        assert!(!params.with_inv_rate(6).is_valid());
+        assert!(!params.with_inv_rate(MAX_INV_RATE * 2).is_valid());
+        assert!(params.with_inv_rate(MAX_INV_RATE).is_valid());
+        assert!(!params.with_fri_fold_po2(0).is_valid());
+        assert!(!params.with_fri_fold_po2(64).is_valid());
+        assert!(!params.with_fri_min_degree(8).is_valid());
+    }
+}
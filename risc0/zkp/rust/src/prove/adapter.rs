@@ -14,8 +14,6 @@
 
 use std::marker::PhantomData;
 
-use rand::thread_rng;
-
 use crate::{
     adapter::{CircuitDef, CircuitStepContext, CustomStep},
     core::{fp::Fp, sha::Sha},
@@ -88,7 +86,7 @@ impl<'a, C: CircuitDef<CS>, CS: CustomStep> Circuit for ProveAdapter<'a, C, CS>
                 .unwrap();
         }
         // Add random noise to end of accum
-        let mut rng = thread_rng();
+        let mut rng = self.exec.zk_rng(1);
         for i in self.steps - ZK_CYCLES..self.steps {
             for j in 0..accum_size {
                 self.accum[j * self.steps + i] = Fp::random(&mut rng);
@@ -16,14 +16,14 @@ use std::cmp::max;
 
 use anyhow::{bail, Result};
 use log::debug;
-use rand::thread_rng;
+use rand::{rngs::StdRng, thread_rng, RngCore, SeedableRng};
 
 use crate::{
     adapter::{CircuitDef, CircuitStepContext, CustomStep},
     core::fp::Fp,
     field::Elem,
     taps::RegisterGroup,
-    MIN_PO2, ZK_CYCLES,
+    MAX_CYCLES_PO2, MIN_PO2, ZK_CYCLES,
 };
 
 pub struct Executor<C: 'static + CircuitDef<S>, S: CustomStep> {
@@ -39,6 +39,21 @@ pub struct Executor<C: 'static + CircuitDef<S>, S: CustomStep> {
     pub halted: bool,
     max_po2: usize,
     pub cycle: usize,
+    /// If set, seeds the randomness used to pad the trace for zero knowledge,
+    /// making the proof deterministic. Such proofs are NOT zero-knowledge and
+    /// must only be used for testing.
+    pub seed: Option<u64>,
+}
+
+/// The trace of an [Executor] whose program has halted, before it is
+/// finalized. It can be saved and restored later with [Executor::from_trace].
+#[derive(Clone, Debug)]
+pub struct ExecutorTrace {
+    pub po2: usize,
+    pub cycle: usize,
+    pub code: Vec<Fp>,
+    pub data: Vec<Fp>,
+    pub output: Vec<Fp>,
 }
 
 impl<C: 'static + CircuitDef<S>, S: CustomStep> Executor<C, S> {
@@ -64,6 +79,53 @@ impl<C: 'static + CircuitDef<S>, S: CustomStep> Executor<C, S> {
             halted: false,
             max_po2,
             cycle: 0,
+            seed: None,
+        }
+    }
+
+    /// Restores an executor from the trace of a halted program, ready to be
+    /// finalized.
+    pub fn from_trace(circuit: &'static C, custom: S, trace: ExecutorTrace) -> Result<Self> {
+        let taps = circuit.get_taps();
+        let code_size = taps.group_size(RegisterGroup::Code);
+        let data_size = taps.group_size(RegisterGroup::Data);
+        if trace.po2 > MAX_CYCLES_PO2 {
+            bail!("Trace po2 of {} is too large", trace.po2);
+        }
+        let steps = 1 << trace.po2;
+        if trace.code.len() != steps * code_size
+            || trace.data.len() != steps * data_size
+            || trace.output.len() != circuit.output_size()
+            || trace.cycle + ZK_CYCLES != steps
+        {
+            bail!("Trace does not match the circuit");
+        }
+        Ok(Executor {
+            circuit,
+            custom,
+            code: trace.code,
+            code_size,
+            data: trace.data,
+            data_size,
+            output: trace.output,
+            po2: trace.po2,
+            steps,
+            halted: true,
+            max_po2: trace.po2,
+            cycle: trace.cycle,
+            seed: None,
+        })
+    }
+
+    /// Returns the trace of a halted program, before it is finalized.
+    pub fn to_trace(&self) -> ExecutorTrace {
+        assert!(self.halted);
+        ExecutorTrace {
+            po2: self.po2,
+            cycle: self.cycle,
+            code: self.code.clone(),
+            data: self.data.clone(),
+            output: self.output.clone(),
         }
     }
 
@@ -132,7 +194,7 @@ impl<C: 'static + CircuitDef<S>, S: CustomStep> Executor<C, S> {
         debug!("finalize");
         assert!(self.halted);
         assert_eq!(self.cycle, self.steps - ZK_CYCLES);
-        let mut rng = thread_rng();
+        let mut rng = self.zk_rng(0);
         // Make code be all zeros of zk cycles, and data be random
         for i in self.cycle..self.steps {
             for j in 0..self.code_size {
@@ -161,6 +223,21 @@ impl<C: 'static + CircuitDef<S>, S: CustomStep> Executor<C, S> {
         }
     }
 
+    /// Returns the source of randomness for the zero-knowledge padding: the
+    /// thread RNG, or a generator derived from [Executor::seed] and `stream`
+    /// so that each use gets an independent sequence.
+    pub(crate) fn zk_rng(&self, stream: u64) -> Box<dyn RngCore> {
+        match self.seed {
+            Some(seed) => {
+                let mut bytes = [0; 32];
+                bytes[..8].copy_from_slice(&seed.to_le_bytes());
+                bytes[8..16].copy_from_slice(&stream.to_le_bytes());
+                Box::new(StdRng::from_seed(bytes))
+            }
+            None => Box::new(thread_rng()),
+        }
+    }
+
     pub fn get_code(&self, cycle: usize, offset: usize) -> Fp {
         self.code[self.steps * offset + cycle]
     }
@@ -25,12 +25,13 @@ use crate::{
     },
     field::Elem,
     hal::{Buffer, Hal},
+    params::ProofParams,
     prove::{merkle::MerkleTreeProver, write_iop::WriteIOP},
-    FRI_FOLD, FRI_MIN_DEGREE, INV_RATE, QUERIES,
 };
 
 struct ProveRoundInfo<H: Hal> {
     domain: usize,
+    fri_fold: usize,
     coeffs: H::BufferFp,
     merkle: MerkleTreeProver<H>,
 }
@@ -41,12 +42,18 @@ impl<H: Hal> ProveRoundInfo<H> {
     /// produce the evaluations of the polynomial, the merkle tree
     /// committing to the evaluation, and the coefficients of the folded
     /// polynomial.
-    pub fn new<S: Sha>(hal: &H, iop: &mut WriteIOP<S>, coeffs: &H::BufferFp) -> Self {
+    pub fn new<S: Sha>(
+        hal: &H,
+        iop: &mut WriteIOP<S>,
+        params: &ProofParams,
+        coeffs: &H::BufferFp,
+    ) -> Self {
         debug!("Doing FRI folding");
+        let fri_fold = params.fri_fold();
         // Get the number of coefficients of the polynomial over the extension field.
         let size = coeffs.size() / EXT_SIZE;
         // Get a larger domain to interpolate over.
-        let domain = size * INV_RATE;
+        let domain = size * params.inv_rate;
         // Allocate space in which to put the interpolated values.
         let evaluated = hal.alloc_fp(domain * EXT_SIZE);
         // Put in the coefficients, padding out with zeros so that we are left with the
@@ -54,25 +61,26 @@ impl<H: Hal> ProveRoundInfo<H> {
         hal.batch_expand(&evaluated, coeffs, EXT_SIZE);
         // Evaluate the NTT in-place, filling the buffer with the evaluations of the
         // polynomial.
-        hal.batch_evaluate_ntt(&evaluated, EXT_SIZE, log2_ceil(INV_RATE));
+        hal.batch_evaluate_ntt(&evaluated, EXT_SIZE, log2_ceil(params.inv_rate));
         // Compute a Merkle tree committing to the polynomial evaluations.
         let merkle = MerkleTreeProver::new(
             hal,
             &evaluated,
-            domain / FRI_FOLD,
-            FRI_FOLD * EXT_SIZE,
-            QUERIES,
+            domain / fri_fold,
+            fri_fold * EXT_SIZE,
+            params.queries,
         );
         // Send the merkle tree (as a commitment) to the virtual IOP verifier
         merkle.commit(hal, iop);
         // Retrieve from the IOP verifier a random value to mix the polynomial slices.
         let fold_mix = Fp4::random(&mut iop.rng);
         // Create a buffer to hold the mixture of slices.
-        let out_coeffs = hal.alloc_fp(size / FRI_FOLD * EXT_SIZE);
+        let out_coeffs = hal.alloc_fp(size / fri_fold * EXT_SIZE);
         // Compute the folded polynomial
         hal.fri_fold(&out_coeffs, coeffs, &fold_mix);
         ProveRoundInfo {
             domain,
+            fri_fold,
             coeffs: out_coeffs,
             merkle,
         }
@@ -80,7 +88,7 @@ impl<H: Hal> ProveRoundInfo<H> {
 
     pub fn prove_query<S: Sha>(&mut self, iop: &mut WriteIOP<S>, pos: &mut usize) {
         // Compute which group we are in
-        let group = *pos % (self.domain / FRI_FOLD);
+        let group = *pos % (self.domain / self.fri_fold);
         // Generate the proof
         self.merkle.prove(iop, group);
         // Update pos
@@ -88,15 +96,20 @@ impl<H: Hal> ProveRoundInfo<H> {
     }
 }
 
-pub fn fri_prove<H: Hal, S: Sha, F>(hal: &H, iop: &mut WriteIOP<S>, coeffs: &H::BufferFp, mut f: F)
-where
+pub fn fri_prove<H: Hal, S: Sha, F>(
+    hal: &H,
+    iop: &mut WriteIOP<S>,
+    params: &ProofParams,
+    coeffs: &H::BufferFp,
+    mut f: F,
+) where
     F: FnMut(&mut WriteIOP<S>, usize),
 {
-    let orig_domain = coeffs.size() / EXT_SIZE * INV_RATE;
+    let orig_domain = coeffs.size() / EXT_SIZE * params.inv_rate;
     let mut rounds = Vec::new();
     let mut coeffs = coeffs.clone();
-    while coeffs.size() / EXT_SIZE > FRI_MIN_DEGREE {
-        let round = ProveRoundInfo::new(hal, iop, &coeffs);
+    while coeffs.size() / EXT_SIZE > params.fri_min_degree {
+        let round = ProveRoundInfo::new(hal, iop, params, &coeffs);
         coeffs = round.coeffs.clone();
         rounds.push(round);
     }
@@ -112,7 +125,7 @@ where
     });
     // Do queries
     debug!("Doing Queries");
-    for _ in 0..QUERIES {
+    for _ in 0..params.queries {
         // Get a 'random' index.
         let rng = iop.rng.next_u32() as usize;
         let mut pos = rng % orig_domain;
@@ -21,7 +21,6 @@ pub mod write_iop;
 
 use alloc::{vec, vec::Vec};
 
-use array_init::array_init;
 use log::debug;
 
 use crate::{
@@ -34,9 +33,10 @@ use crate::{
     },
     field::Elem,
     hal::{Buffer, Hal},
+    params::ProofParams,
     prove::{fri::fri_prove, poly_group::PolyGroup, write_iop::WriteIOP},
     taps::{RegisterGroup, TapSet},
-    CHECK_SIZE, INV_RATE, MAX_CYCLES_PO2,
+    MAX_CYCLES_PO2, ZK_CYCLES,
 };
 
 pub trait Circuit {
@@ -64,42 +64,115 @@ pub trait Circuit {
     fn get_steps(&self) -> usize;
 }
 
+/// A phase of [prove_with_monitor], reported to the monitor as it starts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProvePhase {
+    /// Finishing the execution trace and writing its outputs.
+    Execute,
+    /// Committing to the code and data polynomials.
+    Commit,
+    /// Computing and committing to the accumulation polynomials.
+    Accumulate,
+    /// Computing and committing to the check polynomial.
+    Check,
+    /// Evaluating at the DEEP point and running the FRI protocol.
+    Fri,
+}
+
+/// The error returned by [prove_with_monitor] when the monitor cancels the
+/// proof.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cancelled;
+
+impl core::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Proof was cancelled")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Cancelled {}
+
 pub fn prove_without_seal<H: Hal, S: Sha, C: Circuit>(_hal: &H, sha: &S, circuit: &mut C) {
     let mut iop = WriteIOP::new(sha);
     circuit.execute(&mut iop);
 }
 
-pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> Vec<u32> {
+pub fn prove<H: Hal, S: Sha, C: Circuit>(
+    hal: &H,
+    sha: &S,
+    circuit: &mut C,
+    params: &ProofParams,
+) -> Vec<u32> {
+    prove_with_monitor(hal, sha, circuit, params, |_| true).unwrap()
+}
+
+/// Like [prove], but calls `monitor` as each [ProvePhase] starts. The proof
+/// is abandoned if the monitor returns false.
+///
+/// Panics unless `params` are valid and use no more than [ZK_CYCLES]
+/// queries; callers taking parameters from users should check them first.
+pub fn prove_with_monitor<H, S, C, F>(
+    hal: &H,
+    sha: &S,
+    circuit: &mut C,
+    params: &ProofParams,
+    mut monitor: F,
+) -> Result<Vec<u32>, Cancelled>
+where
+    H: Hal,
+    S: Sha,
+    C: Circuit,
+    F: FnMut(ProvePhase) -> bool,
+{
+    let mut enter = |phase| {
+        if monitor(phase) {
+            Ok(())
+        } else {
+            Err(Cancelled)
+        }
+    };
+
+    assert!(params.is_valid());
+    assert!(params.queries <= ZK_CYCLES);
     let taps = circuit.get_taps();
     let code_size = taps.group_size(RegisterGroup::Code);
     let data_size = taps.group_size(RegisterGroup::Data);
     let accum_size = taps.group_size(RegisterGroup::Accum);
     let mut iop = WriteIOP::new(sha);
 
+    enter(ProvePhase::Execute)?;
     circuit.execute(&mut iop);
 
     let po2 = circuit.po2();
     assert!(po2 as usize <= MAX_CYCLES_PO2);
     let size = 1 << po2;
 
+    // Record the proof parameters so the verifier can check them
+    let params_words = params.to_words();
+    iop.write_u32_slice(&params_words);
+    iop.commit(&sha.hash_words(&params_words));
+
     // Make code + data PolyGroups + commit them
+    enter(ProvePhase::Commit)?;
     let code_coeffs = make_coeffs(hal, circuit.get_code(), code_size);
-    let code_group = PolyGroup::new(hal, &code_coeffs, code_size, size);
+    let code_group = PolyGroup::new(hal, &code_coeffs, code_size, size, params);
     code_group.merkle.commit(hal, &mut iop);
     debug!("codeGroup: {}", code_group.merkle.root());
 
     let data_coeffs = make_coeffs(hal, circuit.get_data(), data_size);
-    let data_group = PolyGroup::new(hal, &data_coeffs, data_size, size);
+    let data_group = PolyGroup::new(hal, &data_coeffs, data_size, size, params);
     data_group.merkle.commit(hal, &mut iop);
     debug!("dataGroup: {}", data_group.merkle.root());
 
+    enter(ProvePhase::Accumulate)?;
     circuit.accumulate(&mut iop);
 
     // Make the accum group + commit
     debug!("size = {size}, accumSize = {accum_size}");
     debug!("getAccum.size() = {}", circuit.get_accum().len());
     let accum_coeffs = make_coeffs(hal, circuit.get_accum(), accum_size);
-    let accum_group = PolyGroup::new(hal, &accum_coeffs, accum_size, size);
+    let accum_group = PolyGroup::new(hal, &accum_coeffs, accum_size, size, params);
     accum_group.merkle.commit(hal, &mut iop);
     debug!("accumGroup: {}", accum_group.merkle.root());
 
@@ -107,7 +180,8 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     let poly_mix = Fp4::random(&mut iop.rng);
 
     // Now generate the check polynomial
-    let domain = size * INV_RATE;
+    enter(ProvePhase::Check)?;
+    let domain = size * params.inv_rate;
     let check_poly = hal.alloc_fp(EXT_SIZE * domain);
     let mix = hal.copy_fp_from(circuit.get_mix());
     let out = hal.copy_fp_from(circuit.get_output());
@@ -155,9 +229,13 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     // are all aleady next to each other and in bit-reversed for for g0, as are
     // the coeffients of g1, etc. So really, we can just reinterpret 4 polys of
     // invRate*size to 16 polys of size, without actually doing anything.
+    //
+    // With a larger invRate the same holds, splitting f(x) into invRate
+    // polynomials of x^invRate instead.
 
     // Make the PolyGroup + add it to the IOP;
-    let check_group = PolyGroup::new(hal, &check_poly, CHECK_SIZE, size);
+    let check_size = params.check_size();
+    let check_group = PolyGroup::new(hal, &check_poly, check_size, size, params);
     check_group.merkle.commit(hal, &mut iop);
     debug!("checkGroup: {}", check_group.merkle.root());
 
@@ -172,6 +250,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     //   LOG(1, "Z = " << Z);
 
     // Get rev rou for size
+    enter(ProvePhase::Fri)?;
     let back_one = Fp4::from_u32(ROU_REV[po2 as usize]);
     let mut all_xs = Vec::new();
 
@@ -218,13 +297,13 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     }
 
     // Add in the coeffs of the check polynomials.
-    let z4 = z.pow(EXT_SIZE);
-    let which: [u32; CHECK_SIZE] = array_init(|i| i as u32);
-    let xs = [z4; CHECK_SIZE];
-    let out = hal.alloc_fp4(CHECK_SIZE);
+    let z4 = z.pow(params.inv_rate);
+    let which: Vec<u32> = (0..check_size as u32).collect();
+    let xs = vec![z4; check_size];
+    let out = hal.alloc_fp4(check_size);
     let which = hal.copy_u32_from(which.as_slice());
     let xs = hal.copy_fp4_from(xs.as_slice());
-    hal.batch_evaluate_any(&check_group.coeffs, CHECK_SIZE, &which, &xs, &out);
+    hal.batch_evaluate_any(&check_group.coeffs, check_size, &which, &xs, &out);
     out.view(|view| {
         coeff_u.extend(view);
     });
@@ -262,7 +341,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     mix_group(RegisterGroup::Code, &code_group);
     mix_group(RegisterGroup::Data, &data_group);
 
-    let which = [combo_count as u32; CHECK_SIZE];
+    let which = vec![combo_count as u32; check_size];
     let which_buf = hal.copy_u32_from(which.as_slice());
     hal.mix_poly_coeffs(
         &combos,
@@ -270,7 +349,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
         &mix,
         &check_group.coeffs,
         &which_buf,
-        CHECK_SIZE,
+        check_size,
         size,
     );
 
@@ -287,7 +366,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
             cur_pos += reg.size();
         }
         // Subtract the final 'check' coefficents
-        for _ in 0..CHECK_SIZE {
+        for _ in 0..check_size {
             combos[size * combo_count] -= cur * coeff_u[cur_pos];
             cur_pos += 1;
             cur *= mix;
@@ -323,7 +402,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     hal.batch_bit_reverse(&final_poly_coeffs, EXT_SIZE);
     debug!("FRI-proof, size = {}", final_poly_coeffs.size() / EXT_SIZE);
 
-    fri_prove(hal, &mut iop, &final_poly_coeffs, |iop, idx| {
+    fri_prove(hal, &mut iop, params, &final_poly_coeffs, |iop, idx| {
         accum_group.merkle.prove(iop, idx);
         code_group.merkle.prove(iop, idx);
         data_group.merkle.prove(iop, idx);
@@ -333,7 +412,7 @@ pub fn prove<H: Hal, S: Sha, C: Circuit>(hal: &H, sha: &S, circuit: &mut C) -> V
     // Return final proof
     let proof = iop.proof;
     debug!("Proof size = {}", proof.len());
-    proof
+    Ok(proof)
 }
 
 fn make_coeffs<H: Hal>(hal: &H, input: &[Fp], count: usize) -> H::BufferFp {
@@ -15,8 +15,8 @@
 use crate::{
     core::log2_ceil,
     hal::{Buffer, Hal},
+    params::ProofParams,
     prove::merkle::MerkleTreeProver,
-    INV_RATE, QUERIES,
 };
 
 /// A PolyGroup represents a group of polynomials, all of the same maximum
@@ -57,14 +57,20 @@ pub struct PolyGroup<'a, H: Hal> {
 }
 
 impl<'a, H: Hal> PolyGroup<'a, H> {
-    pub fn new(hal: &H, coeffs: &'a H::BufferFp, count: usize, size: usize) -> Self {
+    pub fn new(
+        hal: &H,
+        coeffs: &'a H::BufferFp,
+        count: usize,
+        size: usize,
+        params: &ProofParams,
+    ) -> Self {
         assert_eq!(coeffs.size(), count * size);
-        let domain = size * INV_RATE;
+        let domain = size * params.inv_rate;
         let evaluated = hal.alloc_fp(count * domain);
         hal.batch_expand(&evaluated, &coeffs, count);
-        hal.batch_evaluate_ntt(&evaluated, count, log2_ceil(INV_RATE));
+        hal.batch_evaluate_ntt(&evaluated, count, log2_ceil(params.inv_rate));
         hal.batch_bit_reverse(&coeffs, count);
-        let merkle = MerkleTreeProver::new(hal, &evaluated, domain, count, QUERIES);
+        let merkle = MerkleTreeProver::new(hal, &evaluated, domain, count, params.queries);
         PolyGroup {
             coeffs,
             count,
@@ -24,10 +24,12 @@ use crate::{
     field::Elem,
     taps::TapSet,
     verify::{read_iop::ReadIOP, Circuit, VerificationError},
+    MAX_CYCLES_PO2, MIN_CYCLES_PO2,
 };
 
 pub struct VerifyAdapter<'a, C: CircuitInfo + PolyExt + TapsProvider> {
     circuit: &'a C,
+    code_roots: &'a [Digest],
     po2: u32,
     steps: usize,
     out: Vec<Fp>,
@@ -35,15 +37,24 @@ pub struct VerifyAdapter<'a, C: CircuitInfo + PolyExt + TapsProvider> {
 }
 
 impl<'a, C: CircuitInfo + PolyExt + TapsProvider> VerifyAdapter<'a, C> {
-    pub fn new(circuit: &'a C) -> Self {
+    /// Constructs a new VerifyAdapter which accepts a code merkle root only if
+    /// it matches the entry of `code_roots` for the proof's po2. The first
+    /// entry of `code_roots` corresponds to a po2 of [MIN_CYCLES_PO2].
+    pub fn new(circuit: &'a C, code_roots: &'a [Digest]) -> Self {
         VerifyAdapter {
             circuit,
+            code_roots,
             po2: 0,
             steps: 0,
             out: Vec::new(),
             mix: Vec::new(),
         }
     }
+
+    /// Returns the circuit outputs read from the seal.
+    pub fn out(&self) -> &[Fp] {
+        &self.out
+    }
 }
 
 impl<'a, C: CircuitInfo + PolyExt + TapsProvider> Circuit for VerifyAdapter<'a, C> {
@@ -51,14 +62,18 @@ impl<'a, C: CircuitInfo + PolyExt + TapsProvider> Circuit for VerifyAdapter<'a,
         self.circuit.get_taps()
     }
 
-    fn execute<S: Sha>(&mut self, iop: &mut ReadIOP<S>) {
+    fn execute<S: Sha>(&mut self, iop: &mut ReadIOP<S>) -> Result<(), VerificationError> {
         // Read the outputs + size
         self.out.resize(self.circuit.output_size(), Fp::ZERO);
-        iop.read_fps(&mut self.out);
+        iop.read_fps(&mut self.out)?;
         let mut slice = [0u32; 1];
-        iop.read_u32s(&mut slice);
+        iop.read_u32s(&mut slice)?;
         self.po2 = slice[0];
+        if self.po2 as usize > MAX_CYCLES_PO2 {
+            return Err(VerificationError::InvalidPo2 { po2: self.po2 });
+        }
         self.steps = 1 << self.po2;
+        Ok(())
     }
 
     fn accumulate<S: Sha>(&mut self, iop: &mut ReadIOP<S>) {
@@ -72,9 +87,14 @@ impl<'a, C: CircuitInfo + PolyExt + TapsProvider> Circuit for VerifyAdapter<'a,
         self.po2
     }
 
-    fn check_code(&self, _root: &Digest) -> Result<(), VerificationError> {
-        // TODO: check code merkle tree
-        Ok(())
+    fn check_code(&self, root: &Digest) -> Result<(), VerificationError> {
+        let expected = (self.po2 as usize)
+            .checked_sub(MIN_CYCLES_PO2)
+            .and_then(|which| self.code_roots.get(which));
+        match expected {
+            Some(expected) if expected == root => Ok(()),
+            _ => Err(VerificationError::MethodVerificationError),
+        }
     }
 
     fn compute_polynomial(&self, u: &[Fp4], poly_mix: Fp4) -> Fp4 {
@@ -0,0 +1,167 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{borrow::Cow, vec, vec::Vec};
+
+use crate::{
+    core::{
+        fp::Fp,
+        log2_ceil,
+        rou::{ROU_FWD, ROU_REV},
+    },
+    field::Elem,
+    params::ProofParams,
+    taps::TapSet,
+    MAX_CYCLES_PO2, MIN_CYCLES_PO2,
+};
+
+/// A register of the tap set, flattened so that verification doesn't walk the
+/// tap set for each seal.
+pub(crate) struct CachedReg {
+    pub group: usize,
+    pub offset: usize,
+    pub combo_id: usize,
+    pub backs: Vec<usize>,
+}
+
+/// The parts of verification that only depend on a seal's po2 and
+/// [ProofParams]: the FRI round domains and the roots of unity used to
+/// evaluate the taps and queries.
+#[derive(Clone)]
+pub(crate) struct FriLayout {
+    pub po2: u32,
+    pub params: ProofParams,
+    /// The root of unity stepping one cycle back, raised to each back of the
+    /// taps.
+    pub back_pows: Vec<Fp>,
+    /// The generator of the evaluation domain.
+    pub gen: Fp,
+    /// The domain of each FRI folding round.
+    pub rounds: Vec<usize>,
+    /// The degree of the final FRI polynomial.
+    pub final_degree: usize,
+    /// The generator of the final polynomial's evaluation domain.
+    pub final_gen: Fp,
+}
+
+impl FriLayout {
+    fn new(po2: u32, params: ProofParams, max_back: usize) -> Self {
+        let back_one = Fp::new(ROU_REV[po2 as usize]);
+        let back_pows = (0..=max_back).map(|back| back_one.pow(back)).collect();
+        let mut degree = 1 << po2;
+        let mut domain = params.inv_rate * degree;
+        let gen = Fp::new(ROU_FWD[log2_ceil(domain)]);
+        let mut rounds = vec![];
+        while degree > params.fri_min_degree {
+            domain /= params.fri_fold();
+            degree /= params.fri_fold();
+            rounds.push(domain);
+        }
+        FriLayout {
+            po2,
+            params,
+            back_pows,
+            gen,
+            rounds,
+            final_degree: degree,
+            final_gen: Fp::new(ROU_FWD[log2_ceil(params.inv_rate * degree)]),
+        }
+    }
+}
+
+/// The tap and FRI setup of a circuit's verifier, computed once and shared by
+/// every seal verified against it.
+///
+/// A cache built with [VerifyCache::with_params] holds the FRI layout of each
+/// po2 for those [ProofParams]; seals using any other parameters still
+/// verify, but have their layout computed on the fly.
+pub struct VerifyCache {
+    pub(crate) taps: &'static TapSet<'static>,
+    pub(crate) regs: Vec<CachedReg>,
+    pub(crate) combos: Vec<Vec<usize>>,
+    max_back: usize,
+    layouts: Vec<FriLayout>,
+}
+
+impl VerifyCache {
+    /// Flattens the registers and combos of the given tap set.
+    pub fn new(taps: &'static TapSet<'static>) -> Self {
+        let regs: Vec<CachedReg> = taps
+            .regs()
+            .map(|reg| CachedReg {
+                group: reg.group() as usize,
+                offset: reg.offset(),
+                combo_id: reg.combo_id(),
+                backs: (0..reg.size()).map(|i| reg.back(i)).collect(),
+            })
+            .collect();
+        let combos: Vec<Vec<usize>> = taps
+            .combos()
+            .map(|combo| combo.slice().iter().map(|back| *back as usize).collect())
+            .collect();
+        let max_back = regs
+            .iter()
+            .flat_map(|reg| reg.backs.iter())
+            .chain(combos.iter().flatten())
+            .copied()
+            .max()
+            .unwrap_or(0);
+        VerifyCache {
+            taps,
+            regs,
+            combos,
+            max_back,
+            layouts: vec![],
+        }
+    }
+
+    /// Precomputes the FRI layout of every po2 for seals produced with
+    /// `params`.
+    pub fn with_params(self, params: ProofParams) -> Self {
+        let layouts = (MIN_CYCLES_PO2..=MAX_CYCLES_PO2)
+            .map(|po2| FriLayout::new(po2 as u32, params, self.max_back))
+            .collect();
+        Self { layouts, ..self }
+    }
+
+    /// Returns the FRI layout of seals of the given po2 and parameters.
+    pub(crate) fn layout(&self, po2: u32, params: &ProofParams) -> Cow<'_, FriLayout> {
+        match self
+            .layouts
+            .iter()
+            .find(|layout| layout.po2 == po2 && layout.params == *params)
+        {
+            Some(layout) => Cow::Borrowed(layout),
+            None => Cow::Owned(FriLayout::new(po2, *params, self.max_back)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fri_layout() {
+        let params = ProofParams::DEFAULT;
+        let layout = FriLayout::new(16, params, 2);
+        // 2^16 cycles fold by 16 down to a final degree of at most 256.
+        assert_eq!(layout.rounds, [1 << 14, 1 << 10]);
+        assert_eq!(layout.final_degree, 1 << 8);
+        assert_eq!(layout.back_pows.len(), 3);
+        assert_eq!(layout.back_pows[0], Fp::ONE);
+        assert_eq!(layout.gen.pow(params.inv_rate << 16), Fp::ONE);
+        assert_eq!(layout.final_gen.pow(params.inv_rate << 8), Fp::ONE);
+    }
+}
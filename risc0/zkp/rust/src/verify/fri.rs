@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::vec;
+use alloc::{vec, vec::Vec};
 
 use rand::RngCore;
 
@@ -22,32 +22,34 @@ use crate::{
         fp4::{Fp4, EXT_SIZE},
         log2_ceil,
         ntt::{bit_reverse, interpolate_ntt},
-        rou::{ROU_FWD, ROU_REV},
+        rou::ROU_REV,
         sha::Sha,
     },
     field::Elem,
-    verify::{merkle::MerkleTreeVerifier, read_iop::ReadIOP},
-    FRI_FOLD, FRI_MIN_DEGREE, INV_RATE, QUERIES,
+    params::ProofParams,
+    verify::{cache::FriLayout, merkle::MerkleTreeVerifier, read_iop::ReadIOP, VerificationError},
 };
 
 /// VerifyRoundInfo contains the data against which the queries for a particular
 /// round are checked. This includes the Merkle tree top row data, as well as
 /// the size of the domain of the polynomial, and the mixing parameter.
-struct VerifyRoundInfo {
+pub struct VerifyRoundInfo {
     domain: usize,
+    fri_fold: usize,
     merkle: MerkleTreeVerifier,
     mix: Fp4,
 }
 
 fn fold_eval(values: &mut [Fp4], mix: Fp4, s: usize, j: usize) -> Fp4 {
+    let fri_fold = values.len();
     interpolate_ntt(values);
     bit_reverse(values);
-    let root_po2 = log2_ceil(FRI_FOLD * s);
+    let root_po2 = log2_ceil(fri_fold * s);
     let inv_wk: Fp = Fp::new(ROU_REV[root_po2]).pow(j);
     let mut mul = Fp::ONE;
     let mut tot = Fp4::ZERO;
     let mut mix_pow = Fp4::ONE;
-    for i in 0..FRI_FOLD {
+    for i in 0..fri_fold {
         tot += values[i] * mul * mix_pow;
         mul *= inv_wk;
         mix_pow *= mix;
@@ -56,66 +58,109 @@ fn fold_eval(values: &mut [Fp4], mix: Fp4, s: usize, j: usize) -> Fp4 {
 }
 
 impl VerifyRoundInfo {
-    pub fn new<S: Sha>(iop: &mut ReadIOP<S>, in_domain: usize) -> Self {
-        let domain = in_domain / FRI_FOLD;
-        VerifyRoundInfo {
+    pub fn new<S: Sha>(
+        iop: &mut ReadIOP<S>,
+        params: &ProofParams,
+        domain: usize,
+    ) -> Result<Self, VerificationError> {
+        let fri_fold = params.fri_fold();
+        Ok(VerifyRoundInfo {
             domain,
-            merkle: MerkleTreeVerifier::new(iop, domain, FRI_FOLD * EXT_SIZE, QUERIES),
+            fri_fold,
+            merkle: MerkleTreeVerifier::new(iop, domain, fri_fold * EXT_SIZE, params.queries)?,
             mix: Fp4::random(iop),
-        }
+        })
+    }
+
+    /// Returns the number of seal words read by each call to
+    /// [VerifyRoundInfo::verify_query].
+    pub fn query_size(&self) -> usize {
+        self.merkle.branch_size()
     }
 
-    pub fn verify_query<S: Sha>(&mut self, iop: &mut ReadIOP<S>, pos: &mut usize, goal: &mut Fp4) {
+    pub fn verify_query<S: Sha>(
+        &mut self,
+        iop: &mut ReadIOP<S>,
+        pos: &mut usize,
+        goal: &mut Fp4,
+    ) -> Result<(), VerificationError> {
         let quot = *pos / self.domain;
         let group = *pos % self.domain;
         // Get the column data
-        let data = self.merkle.verify(iop, group);
+        let data = self.merkle.verify(iop, group)?;
+        let fri_fold = self.fri_fold;
         let mut data4 = vec![];
-        for i in 0..FRI_FOLD {
+        for i in 0..fri_fold {
             data4.push(Fp4::new(
-                data[0 * FRI_FOLD + i],
-                data[1 * FRI_FOLD + i],
-                data[2 * FRI_FOLD + i],
-                data[3 * FRI_FOLD + i],
+                data[0 * fri_fold + i],
+                data[1 * fri_fold + i],
+                data[2 * fri_fold + i],
+                data[3 * fri_fold + i],
             ));
         }
         // Check the existing goal
-        assert_eq!(data4[quot], *goal);
+        if data4[quot] != *goal {
+            return Err(VerificationError::FriFoldMismatch);
+        }
         // Compute the new goal + pos
         *goal = fold_eval(&mut data4, self.mix, self.domain, group);
         *pos = group;
+        Ok(())
     }
 }
 
-pub fn fri_verify<S: Sha, F>(iop: &mut ReadIOP<S>, mut degree: usize, mut inner: F)
-where
-    F: FnMut(&mut ReadIOP<S>, usize) -> Fp4,
-{
-    let orig_domain = INV_RATE * degree;
-    let mut domain = orig_domain;
+/// Reads the commitments for each folding round, given the domain of each
+/// round.
+pub fn read_rounds<S: Sha>(
+    iop: &mut ReadIOP<S>,
+    params: &ProofParams,
+    domains: &[usize],
+) -> Result<Vec<VerifyRoundInfo>, VerificationError> {
     // Prep the folding verfiers
-    let mut rounds = vec![];
-    while degree > FRI_MIN_DEGREE {
-        rounds.push(VerifyRoundInfo::new(iop, domain));
-        domain /= FRI_FOLD;
-        degree /= FRI_FOLD;
-    }
+    domains
+        .iter()
+        .map(|domain| VerifyRoundInfo::new(iop, params, *domain))
+        .collect()
+}
+
+/// Reads and commits to the coefficients of the final polynomial.
+pub fn read_final_coeffs<S: Sha>(
+    iop: &mut ReadIOP<S>,
+    degree: usize,
+) -> Result<Vec<Fp>, VerificationError> {
     // Grab the final coeffs + commit
     let mut final_coeffs = vec![Fp::ZERO; EXT_SIZE * degree];
-    iop.read_fps(&mut final_coeffs);
+    iop.read_fps(&mut final_coeffs)?;
     let final_digest = iop.get_sha().hash_fps(&final_coeffs); // padding?
     iop.commit(&final_digest);
-    // Get the generator for the final polynomial evaluations
-    let gen = Fp::new(ROU_FWD[log2_ceil(domain)]);
+    Ok(final_coeffs)
+}
+
+/// Runs the FRI queries against the rounds and final coefficients read by
+/// [read_rounds] and [read_final_coeffs] for a seal with the given layout.
+pub fn fri_verify<S: Sha, F>(
+    iop: &mut ReadIOP<S>,
+    params: &ProofParams,
+    layout: &FriLayout,
+    rounds: &mut [VerifyRoundInfo],
+    final_coeffs: &[Fp],
+    mut inner: F,
+) -> Result<(), VerificationError>
+where
+    F: FnMut(&mut ReadIOP<S>, usize) -> Result<Fp4, VerificationError>,
+{
+    let orig_domain = params.inv_rate << layout.po2;
+    let degree = layout.final_degree;
+    let gen = layout.final_gen;
     // Do queries
-    for _ in 0..QUERIES {
+    for _ in 0..params.queries {
         let rng = iop.next_u32();
         let mut pos = rng as usize % orig_domain;
         // Do the 'inner' verification for this index
-        let mut goal = inner(iop, pos);
+        let mut goal = inner(iop, pos)?;
         // Verify the per-round proofs
-        for round in &mut rounds {
-            round.verify_query(iop, &mut pos, &mut goal);
+        for round in rounds.iter_mut() {
+            round.verify_query(iop, &mut pos, &mut goal)?;
         }
         // Do final verification
         let x = gen.pow(pos);
@@ -131,6 +176,9 @@ where
             fx += cur * coeff;
             cur *= x;
         }
-        assert_eq!(fx, goal)
+        if fx != goal {
+            return Err(VerificationError::FriFinalMismatch);
+        }
     }
+    Ok(())
 }
@@ -17,11 +17,11 @@ use alloc::{vec, vec::Vec};
 use crate::{
     core::{
         fp::Fp,
-        sha::{Digest, Sha},
+        sha::{Digest, Sha, DIGEST_WORDS},
     },
     field::Elem,
     merkle::MerkleTreeParams,
-    verify::read_iop::ReadIOP,
+    verify::{read_iop::ReadIOP, VerificationError},
 };
 
 /// A struct against which we verify merkle branches, consisting of the
@@ -40,21 +40,21 @@ impl MerkleTreeVerifier {
         row_size: usize,
         col_size: usize,
         queries: usize,
-    ) -> Self {
+    ) -> Result<Self, VerificationError> {
         let params = MerkleTreeParams::new(row_size, col_size, queries);
         // Initialize a vector to hold the digests.
         // Vector is twice as long as the "top" row - the children of the entry at index
         // i are stored at 2*i and 2*i+1.
         let mut top = vec![Digest::default(); params.top_size * 2];
         // Fill top vector with digests from IOP.
-        iop.read_digests(&mut top[params.top_size..]);
+        iop.read_digests(&mut top[params.top_size..])?;
         // Populate hashes up to the root of the tree.
         for i in (1..params.top_size).rev() {
             top[i] = *iop.get_sha().hash_pair(&top[2 * i], &top[2 * i + 1]);
         }
         // Commit to root (index 1).
         iop.commit(&top[1]);
-        MerkleTreeVerifier { params, top }
+        Ok(MerkleTreeVerifier { params, top })
     }
 
     /// Returns the root hash of the tree.
@@ -62,13 +62,28 @@ impl MerkleTreeVerifier {
         &self.top[1]
     }
 
+    /// Returns the number of seal words read by each call to
+    /// [MerkleTreeVerifier::verify].
+    pub fn branch_size(&self) -> usize {
+        self.params.col_size + (self.params.layers - self.params.top_layer) * DIGEST_WORDS
+    }
+
     /// Verifies a branch provided by an IOP.
-    pub fn verify<S: Sha>(&self, iop: &mut ReadIOP<S>, mut idx: usize) -> Vec<Fp> {
-        assert!(idx < self.params.row_size);
+    pub fn verify<S: Sha>(
+        &self,
+        iop: &mut ReadIOP<S>,
+        mut idx: usize,
+    ) -> Result<Vec<Fp>, VerificationError> {
+        if idx >= self.params.row_size {
+            return Err(VerificationError::MerkleQueryOutOfRange {
+                idx,
+                rows: self.params.row_size,
+            });
+        }
         // Initialize a vector to hold field elements.
         let mut out = vec![Fp::ZERO; self.params.col_size];
         // Read out field elements from IOP.
-        iop.read_fps(&mut out);
+        iop.read_fps(&mut out)?;
         // Get the hash at the leaf of the tree by hashing these field elements.
         let mut cur = *iop.get_sha().hash_fps(&out);
         // Shift idx to start of the row
@@ -79,7 +94,7 @@ impl MerkleTreeVerifier {
             let low_bit = idx % 2;
             // Retrieve the other parent from the IOP.
             let mut other = [Digest::default(); 1];
-            iop.read_digests(&mut other);
+            iop.read_digests(&mut other)?;
             // Now ascend to the parent index, and compute the hash there.
             idx /= 2;
             if low_bit == 1 {
@@ -90,7 +105,9 @@ impl MerkleTreeVerifier {
         }
         // Once we reduce to an index for which we have the hash, check that it's
         // correct.
-        assert_eq!(self.top[idx], cur);
-        out
+        if self.top[idx] != cur {
+            return Err(VerificationError::MerkleBranchMismatch);
+        }
+        Ok(out)
     }
 }
@@ -13,35 +13,52 @@
 // limitations under the License.
 
 pub mod adapter;
+mod cache;
 mod fri;
 mod merkle;
 pub mod read_iop;
 
-use alloc::vec;
+use alloc::{borrow::Cow, vec, vec::Vec};
 use core::fmt;
 // use log::debug;
 
 use crate::{
     core::{
         fp::Fp,
-        fp4::{Fp4, EXT_SIZE},
+        fp4::Fp4,
         log2_ceil,
+        ntt::bit_rev_32,
         poly::poly_eval,
-        rou::{ROU_FWD, ROU_REV},
         sha::{Digest, Sha},
     },
     field::Elem,
+    params::{ProofParams, PROOF_PARAMS_WORDS},
     taps::{RegisterGroup, TapSet},
-    verify::{fri::fri_verify, merkle::MerkleTreeVerifier, read_iop::ReadIOP},
-    INV_RATE, MAX_CYCLES_PO2, QUERIES,
+    verify::{
+        cache::FriLayout,
+        fri::{fri_verify, read_final_coeffs, read_rounds, VerifyRoundInfo},
+        merkle::MerkleTreeVerifier,
+        read_iop::ReadIOP,
+    },
+    MAX_CYCLES_PO2,
 };
 
-const CHECK_SIZE: usize = INV_RATE * EXT_SIZE;
+pub use self::cache::VerifyCache;
 
 #[derive(Debug)]
 pub enum VerificationError {
     ReceiptFormatError,
     MethodVerificationError,
+    InvalidPo2 { po2: u32 },
+    InvalidProofParams,
+    InsufficientSecurity { bits: usize, min_bits: usize },
+    UnexpectedEndOfSeal,
+    TrailingSealData { words: usize },
+    CheckPolynomialMismatch,
+    MerkleQueryOutOfRange { idx: usize, rows: usize },
+    MerkleBranchMismatch,
+    FriFoldMismatch,
+    FriFinalMismatch,
 }
 
 impl fmt::Display for VerificationError {
@@ -49,20 +66,157 @@ impl fmt::Display for VerificationError {
         match self {
             VerificationError::ReceiptFormatError => write!(f, "invalid receipt format"),
             VerificationError::MethodVerificationError => write!(f, "method verification failed"),
+            VerificationError::InvalidPo2 { po2 } => {
+                write!(f, "invalid po2 {po2}, max is {MAX_CYCLES_PO2}")
+            }
+            VerificationError::InvalidProofParams => write!(f, "invalid proof parameters"),
+            VerificationError::InsufficientSecurity { bits, min_bits } => write!(
+                f,
+                "proof has {bits} bits of conjectured security, {min_bits} are required"
+            ),
+            VerificationError::UnexpectedEndOfSeal => write!(f, "seal ended unexpectedly"),
+            VerificationError::TrailingSealData { words } => {
+                write!(f, "{words} unread words at end of seal")
+            }
+            VerificationError::CheckPolynomialMismatch => {
+                write!(f, "check polynomial does not match the constraints")
+            }
+            VerificationError::MerkleQueryOutOfRange { idx, rows } => {
+                write!(f, "merkle query {idx} out of range, tree has {rows} rows")
+            }
+            VerificationError::MerkleBranchMismatch => write!(f, "invalid merkle branch"),
+            VerificationError::FriFoldMismatch => write!(f, "FRI fold does not match goal"),
+            VerificationError::FriFinalMismatch => {
+                write!(f, "FRI final polynomial does not match goal")
+            }
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for VerificationError {}
+
 pub trait Circuit {
     fn taps(&self) -> &'static TapSet<'static>;
-    fn execute<S: Sha>(&mut self, iop: &mut ReadIOP<S>);
+    fn execute<S: Sha>(&mut self, iop: &mut ReadIOP<S>) -> Result<(), VerificationError>;
     fn accumulate<S: Sha>(&mut self, iop: &mut ReadIOP<S>);
     fn po2(&self) -> u32;
     fn check_code(&self, root: &Digest) -> Result<(), VerificationError>;
     fn compute_polynomial(&self, u: &[Fp4], mix: Fp4) -> Fp4;
 }
 
-pub fn verify<S, C>(sha: &S, circuit: &mut C, seal: &[u32]) -> Result<(), VerificationError>
+/// The number of words taken by each section of a seal, in the order they
+/// appear.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SealSizes {
+    /// The circuit outputs and po2, read by [Circuit::execute].
+    pub header: usize,
+    /// The [ProofParams] the seal was produced with.
+    pub params: usize,
+    /// The top rows of the code Merkle tree.
+    pub code: usize,
+    /// The top rows of the data Merkle tree.
+    pub data: usize,
+    /// The top rows of the accum Merkle tree.
+    pub accum: usize,
+    /// The top rows of the check Merkle tree.
+    pub check: usize,
+    /// The coefficients of the taps and check polynomial.
+    pub coeffs: usize,
+    /// The Merkle tree tops of each FRI folding round.
+    pub fri_rounds: usize,
+    /// The coefficients of the final FRI polynomial.
+    pub fri_final: usize,
+    /// The Merkle branches opened by the queries.
+    pub queries: usize,
+}
+
+impl SealSizes {
+    /// Returns the total number of words in the seal.
+    pub fn total(&self) -> usize {
+        self.header
+            + self.params
+            + self.code
+            + self.data
+            + self.accum
+            + self.check
+            + self.coeffs
+            + self.fri_rounds
+            + self.fri_final
+            + self.queries
+    }
+}
+
+/// The layout of a seal, as parsed by [inspect].
+#[derive(Clone, Debug)]
+pub struct SealInfo {
+    pub po2: u32,
+    pub params: ProofParams,
+    pub code_root: Digest,
+    pub data_root: Digest,
+    pub accum_root: Digest,
+    pub check_root: Digest,
+    pub sizes: SealSizes,
+}
+
+/// Everything [read_seal] reads from a seal ahead of the FRI queries.
+struct SealCommitments<'c> {
+    po2: u32,
+    params: ProofParams,
+    code_merkle: MerkleTreeVerifier,
+    data_merkle: MerkleTreeVerifier,
+    accum_merkle: MerkleTreeVerifier,
+    check_merkle: MerkleTreeVerifier,
+    poly_mix: Fp4,
+    z: Fp4,
+    coeff_u: Vec<Fp4>,
+    mix: Fp4,
+    rounds: Vec<VerifyRoundInfo>,
+    final_coeffs: Vec<Fp>,
+    layout: Cow<'c, FriLayout>,
+    sizes: SealSizes,
+}
+
+impl<'c> SealCommitments<'c> {
+    /// Returns the number of seal words taken by the FRI queries.
+    fn query_size(&self) -> Result<usize, VerificationError> {
+        let branches = [
+            &self.accum_merkle,
+            &self.code_merkle,
+            &self.data_merkle,
+            &self.check_merkle,
+        ];
+        let sizes = branches
+            .iter()
+            .map(|merkle| merkle.branch_size())
+            .chain(self.rounds.iter().map(VerifyRoundInfo::query_size));
+        total_query_size(self.params.queries, sizes)
+    }
+}
+
+/// Returns `queries` times the sum of `sizes`. The number of queries comes
+/// from the seal, so a total that does not fit in a `usize`, as can happen on
+/// 32-bit targets, is reported as [VerificationError::InvalidProofParams].
+fn total_query_size<I>(queries: usize, sizes: I) -> Result<usize, VerificationError>
+where
+    I: IntoIterator<Item = usize>,
+{
+    sizes
+        .into_iter()
+        .try_fold(0usize, usize::checked_add)
+        .and_then(|size| size.checked_mul(queries))
+        .ok_or(VerificationError::InvalidProofParams)
+}
+
+/// Reads a seal up to the FRI queries, committing to each section in the
+/// order the prover wrote it. Neither the code root nor any of the constraints
+/// are checked here, and [SealSizes::queries] is left for the caller.
+fn read_seal<'a, 'c, S, C>(
+    sha: &'a S,
+    circuit: &mut C,
+    cache: &'c VerifyCache,
+    seal: &'a [u32],
+) -> Result<(ReadIOP<'a, S>, SealCommitments<'c>), VerificationError>
 where
     S: Sha,
     C: Circuit,
@@ -70,152 +224,338 @@ where
     if seal.len() == 0 {
         return Err(VerificationError::ReceiptFormatError);
     }
-    let taps = circuit.taps();
+    let taps = cache.taps;
 
     // Make IOP
     let mut iop = ReadIOP::new(sha, seal);
+    let mut sizes = SealSizes::default();
+    let mut mark = iop.remaining();
+    let mut section = |iop: &ReadIOP<S>| {
+        let words = mark - iop.remaining();
+        mark = iop.remaining();
+        words
+    };
 
     // Read any execution state
-    circuit.execute(&mut iop);
+    circuit.execute(&mut iop)?;
+    sizes.header = section(&iop);
 
     // Get the size
     let po2 = circuit.po2();
-    assert!(po2 as usize <= MAX_CYCLES_PO2);
+    if po2 as usize > MAX_CYCLES_PO2 {
+        return Err(VerificationError::InvalidPo2 { po2 });
+    }
     let size = 1 << po2;
-    let domain = INV_RATE * size;
     // debug!("size = {size}, po2 = {po2}");
 
-    // Get taps and compute sizes
-    let code_size = taps.group_size(RegisterGroup::Code);
-    let data_size = taps.group_size(RegisterGroup::Data);
-    let accum_size = taps.group_size(RegisterGroup::Accum);
-    let combo_count = taps.combos_size();
+    // Read the proof parameters
+    let params = read_params(&mut iop)?;
+    sizes.params = section(&iop);
+    let layout = cache.layout(po2, &params);
+    let domain = params.inv_rate * size;
+    let queries = params.queries;
 
     // Get code and data merkle roots
-    let code_merkle = MerkleTreeVerifier::new(&mut iop, domain, code_size, QUERIES);
+    let code_size = taps.group_size(RegisterGroup::Code);
+    let code_merkle = MerkleTreeVerifier::new(&mut iop, domain, code_size, queries)?;
+    sizes.code = section(&iop);
     // debug!("codeRoot = {}", code_merkle.root());
-    let data_merkle = MerkleTreeVerifier::new(&mut iop, domain, data_size, QUERIES);
+    let data_size = taps.group_size(RegisterGroup::Data);
+    let data_merkle = MerkleTreeVerifier::new(&mut iop, domain, data_size, queries)?;
+    sizes.data = section(&iop);
     // debug!("dataRoot = {}", data_merkle.root());
 
-    // Verify code is valid
-    circuit.check_code(code_merkle.root())?;
-
     // Prep accumulation
     circuit.accumulate(&mut iop);
 
-    let accum_merkle = MerkleTreeVerifier::new(&mut iop, domain, accum_size, QUERIES);
+    let accum_size = taps.group_size(RegisterGroup::Accum);
+    let accum_merkle = MerkleTreeVerifier::new(&mut iop, domain, accum_size, queries)?;
+    sizes.accum = section(&iop);
     // debug!("accumRoot = {}", accum_merkle.root());
 
     // Set the poly mix value
     let poly_mix = Fp4::random(&mut iop);
 
-    let check_merkle = MerkleTreeVerifier::new(&mut iop, domain, CHECK_SIZE, QUERIES);
+    let check_merkle = MerkleTreeVerifier::new(&mut iop, domain, params.check_size(), queries)?;
+    sizes.check = section(&iop);
     // debug!("checkRoot = {}", check_merkle.root());
 
     let z = Fp4::random(&mut iop);
     // debug!("Z = {z:?}");
-    let back_one = Fp::from(ROU_REV[po2 as usize]);
 
     // Read the U coeffs + commit their hash
-    let num_taps = taps.tap_size();
-    let mut coeff_u = vec![Fp4::ZERO; num_taps + CHECK_SIZE];
-    iop.read_fp4s(&mut coeff_u);
+    let mut coeff_u = vec![Fp4::ZERO; taps.tap_size() + params.check_size()];
+    iop.read_fp4s(&mut coeff_u)?;
     let hash_u = *sha.hash_fp4s(&coeff_u);
     iop.commit(&hash_u);
+    sizes.coeffs = section(&iop);
+
+    // Set the mix mix value
+    let mix = Fp4::random(&mut iop);
+    // debug!("mix = {mix:?}");
+
+    // Read the FRI commitments
+    let rounds = read_rounds(&mut iop, &params, &layout.rounds)?;
+    sizes.fri_rounds = section(&iop);
+    let final_coeffs = read_final_coeffs(&mut iop, layout.final_degree)?;
+    sizes.fri_final = section(&iop);
+
+    Ok((
+        iop,
+        SealCommitments {
+            po2,
+            params,
+            code_merkle,
+            data_merkle,
+            accum_merkle,
+            check_merkle,
+            poly_mix,
+            z,
+            coeff_u,
+            mix,
+            rounds,
+            final_coeffs,
+            layout,
+            sizes,
+        },
+    ))
+}
+
+/// Parses a seal the same way [verify] does, without checking the code root,
+/// any of the constraints or the FRI queries. This is much cheaper than a
+/// full verification, but says nothing about the validity of the seal beyond
+/// its layout.
+pub fn inspect<S, C>(sha: &S, circuit: &mut C, seal: &[u32]) -> Result<SealInfo, VerificationError>
+where
+    S: Sha,
+    C: Circuit,
+{
+    let cache = VerifyCache::new(circuit.taps());
+    let (iop, mut seal) = read_seal(sha, circuit, &cache, seal)?;
+    let queries = seal.query_size()?;
+    if iop.remaining() < queries {
+        return Err(VerificationError::UnexpectedEndOfSeal);
+    }
+    if iop.remaining() > queries {
+        return Err(VerificationError::TrailingSealData {
+            words: iop.remaining() - queries,
+        });
+    }
+    seal.sizes.queries = queries;
+
+    Ok(SealInfo {
+        po2: seal.po2,
+        params: seal.params,
+        code_root: *seal.code_merkle.root(),
+        data_root: *seal.data_merkle.root(),
+        accum_root: *seal.accum_merkle.root(),
+        check_root: *seal.check_merkle.root(),
+        sizes: seal.sizes,
+    })
+}
+
+/// Reads and commits to the [ProofParams] following the circuit header,
+/// checking that they are usable.
+fn read_params<S: Sha>(iop: &mut ReadIOP<S>) -> Result<ProofParams, VerificationError> {
+    let mut words = [0; PROOF_PARAMS_WORDS];
+    iop.read_u32s(&mut words)?;
+    let hash = *iop.get_sha().hash_words(&words);
+    iop.commit(&hash);
+    let params = ProofParams::from_words(&words);
+    if !params.is_valid() {
+        return Err(VerificationError::InvalidProofParams);
+    }
+    Ok(params)
+}
+
+/// Verifies a seal, requiring at least the conjectured security of the
+/// default [ProofParams].
+pub fn verify<S, C>(sha: &S, circuit: &mut C, seal: &[u32]) -> Result<(), VerificationError>
+where
+    S: Sha,
+    C: Circuit,
+{
+    verify_with_min_security(sha, circuit, seal, ProofParams::DEFAULT.security_bits())
+}
+
+/// Verifies a seal, requiring its [ProofParams] to provide at least
+/// `min_security_bits` of conjectured security.
+pub fn verify_with_min_security<S, C>(
+    sha: &S,
+    circuit: &mut C,
+    seal: &[u32],
+    min_security_bits: usize,
+) -> Result<(), VerificationError>
+where
+    S: Sha,
+    C: Circuit,
+{
+    let cache = VerifyCache::new(circuit.taps());
+    verify_with_cache(sha, circuit, &cache, seal, min_security_bits)
+}
+
+/// Verifies a seal as [verify_with_min_security] does, reusing the tap and
+/// FRI setup of `cache` rather than recomputing it for this seal.
+pub fn verify_with_cache<S, C>(
+    sha: &S,
+    circuit: &mut C,
+    cache: &VerifyCache,
+    seal: &[u32],
+    min_security_bits: usize,
+) -> Result<(), VerificationError>
+where
+    S: Sha,
+    C: Circuit,
+{
+    let (mut iop, mut seal) = read_seal(sha, circuit, cache, seal)?;
+    let params = &seal.params;
+
+    // Check the proof parameters are secure enough
+    if params.security_bits() < min_security_bits {
+        return Err(VerificationError::InsufficientSecurity {
+            bits: params.security_bits(),
+            min_bits: min_security_bits,
+        });
+    }
+
+    // Verify code is valid
+    circuit.check_code(seal.code_merkle.root())?;
+
+    let size = 1 << seal.po2;
+    let check_size = params.check_size();
+    let combo_count = cache.combos.len();
+    let (z, mix, coeff_u) = (seal.z, seal.mix, &seal.coeff_u);
+    let layout = &seal.layout;
+    let back_pows = &layout.back_pows;
 
     // Now, convert to evaluated values
+    let num_taps = cache.taps.tap_size();
     let mut cur_pos = 0;
     let mut eval_u = vec![];
-    for reg in taps.regs() {
-        for i in 0..reg.size() {
-            let x = back_one.pow(reg.back(i)) * z;
-            let fx = poly_eval(&coeff_u[cur_pos..(cur_pos + reg.size())], x);
+    for reg in &cache.regs {
+        for back in &reg.backs {
+            let x = back_pows[*back] * z;
+            let fx = poly_eval(&coeff_u[cur_pos..(cur_pos + reg.backs.len())], x);
             eval_u.push(fx);
         }
-        cur_pos += reg.size();
+        cur_pos += reg.backs.len();
     }
 
     // Compute the core polynomial
-    let result = circuit.compute_polynomial(&eval_u, poly_mix);
+    let result = circuit.compute_polynomial(&eval_u, seal.poly_mix);
     // debug!("Result = {result:?}");
 
-    // Now generate the check polynomial
+    // Now generate the check polynomial. The check group holds inv_rate
+    // polynomials for each element of Fp4, in bit reversed order.
     let mut check = Fp4::ZERO;
-    let remap = [0, 2, 1, 3];
+    let inv_rate = params.inv_rate;
+    let rate_po2 = log2_ceil(inv_rate);
     let fp0 = Fp::from(0 as u32);
     let fp1 = Fp::from(1 as u32);
-    for i in 0..4 {
-        let rmi = remap[i];
-        check += coeff_u[num_taps + rmi + 0] * z.pow(i) * Fp4::new(fp1, fp0, fp0, fp0);
-        check += coeff_u[num_taps + rmi + 4] * z.pow(i) * Fp4::new(fp0, fp1, fp0, fp0);
-        check += coeff_u[num_taps + rmi + 8] * z.pow(i) * Fp4::new(fp0, fp0, fp1, fp0);
-        check += coeff_u[num_taps + rmi + 12] * z.pow(i) * Fp4::new(fp0, fp0, fp0, fp1);
+    for i in 0..inv_rate {
+        let rmi = (bit_rev_32(i as u32) >> (32 - rate_po2)) as usize;
+        check += coeff_u[num_taps + rmi + 0 * inv_rate] * z.pow(i) * Fp4::new(fp1, fp0, fp0, fp0);
+        check += coeff_u[num_taps + rmi + 1 * inv_rate] * z.pow(i) * Fp4::new(fp0, fp1, fp0, fp0);
+        check += coeff_u[num_taps + rmi + 2 * inv_rate] * z.pow(i) * Fp4::new(fp0, fp0, fp1, fp0);
+        check += coeff_u[num_taps + rmi + 3 * inv_rate] * z.pow(i) * Fp4::new(fp0, fp0, fp0, fp1);
     }
     check *= (Fp4::from_u32(3) * z).pow(size) - Fp4::ONE;
     // debug!("Check = {check:?}");
-    assert_eq!(check, result);
-
-    // Set the mix mix value
-    let mix = Fp4::random(&mut iop);
-    // debug!("mix = {mix:?}");
+    if check != result {
+        return Err(VerificationError::CheckPolynomialMismatch);
+    }
 
     // Make the mixed U polynomials
     let mut combo_u = vec![];
-    for i in 0..combo_count {
-        combo_u.push(vec![Fp4::ZERO; taps.get_combo(i).size()]);
+    for combo in &cache.combos {
+        combo_u.push(vec![Fp4::ZERO; combo.len()]);
     }
     let mut cur_mix = Fp4::ONE;
     cur_pos = 0;
-    for reg in taps.regs() {
-        for i in 0..reg.size() {
-            combo_u[reg.combo_id()][i] += cur_mix * coeff_u[cur_pos + i];
+    for reg in &cache.regs {
+        for i in 0..reg.backs.len() {
+            combo_u[reg.combo_id][i] += cur_mix * coeff_u[cur_pos + i];
         }
         cur_mix *= mix;
-        cur_pos += reg.size();
+        cur_pos += reg.backs.len();
     }
     // debug!("cur_mix: {cur_mix:?}, cur_pos: {cur_pos}");
     // Handle check group
     combo_u.push(vec![Fp4::ZERO]);
-    for _ in 0..CHECK_SIZE {
+    for _ in 0..check_size {
         combo_u[combo_count][0] += cur_mix * coeff_u[cur_pos];
         cur_pos += 1;
         cur_mix *= mix;
     }
     // debug!("cur_mix: {cur_mix:?}");
 
-    let gen = Fp::new(ROU_FWD[log2_ceil(domain)]);
+    // The combo divisor roots only depend on z, so compute them once rather
+    // than on every query
+    let combo_roots: Vec<Vec<Fp4>> = cache
+        .combos
+        .iter()
+        .map(|backs| backs.iter().map(|back| z * back_pows[*back]).collect())
+        .collect();
+    let check_root = z.pow(inv_rate);
+    let gen = layout.gen;
+    let (accum_merkle, code_merkle) = (&seal.accum_merkle, &seal.code_merkle);
+    let (data_merkle, check_merkle) = (&seal.data_merkle, &seal.check_merkle);
     // debug!("FRI-verify, size = {size}");
-    fri_verify(&mut iop, size, |iop: &mut ReadIOP<S>, idx: usize| -> Fp4 {
-        let x = Fp4::from_fp(gen.pow(idx));
-        let mut rows = vec![];
-        rows.push(accum_merkle.verify(iop, idx));
-        rows.push(code_merkle.verify(iop, idx));
-        rows.push(data_merkle.verify(iop, idx));
-        let check_row = check_merkle.verify(iop, idx);
-        let mut cur = Fp4::ONE;
-        let mut tot = vec![Fp4::ZERO; combo_count + 1];
-        for reg in taps.regs() {
-            tot[reg.combo_id()] += cur * rows[reg.group() as usize][reg.offset()];
-            cur *= mix;
-        }
-        for i in 0..CHECK_SIZE {
-            tot[combo_count] += cur * check_row[i];
-            cur *= mix;
-        }
-        let mut ret = Fp4::ZERO;
-        for i in 0..combo_count {
-            let num = tot[i] - poly_eval(&combo_u[i], x);
-            let mut divisor = Fp4::ONE;
-            for back in taps.get_combo(i).slice() {
-                divisor *= x - z * back_one.pow(*back as usize);
+    fri_verify(
+        &mut iop,
+        params,
+        layout,
+        &mut seal.rounds,
+        &seal.final_coeffs,
+        |iop, idx| {
+            let x = Fp4::from_fp(gen.pow(idx));
+            let mut rows = vec![];
+            rows.push(accum_merkle.verify(iop, idx)?);
+            rows.push(code_merkle.verify(iop, idx)?);
+            rows.push(data_merkle.verify(iop, idx)?);
+            let check_row = check_merkle.verify(iop, idx)?;
+            let mut cur = Fp4::ONE;
+            let mut tot = vec![Fp4::ZERO; combo_count + 1];
+            for reg in &cache.regs {
+                tot[reg.combo_id] += cur * rows[reg.group][reg.offset];
+                cur *= mix;
             }
-            ret += num * divisor.inv();
-        }
-        let check_num = tot[combo_count] - combo_u[combo_count][0];
-        let check_div = x - z.pow(INV_RATE);
-        ret += check_num * check_div.inv();
-        ret
-    });
-    iop.verify_complete();
-    Ok(())
+            for i in 0..check_size {
+                tot[combo_count] += cur * check_row[i];
+                cur *= mix;
+            }
+            let mut ret = Fp4::ZERO;
+            for i in 0..combo_count {
+                let num = tot[i] - poly_eval(&combo_u[i], x);
+                let mut divisor = Fp4::ONE;
+                for root in &combo_roots[i] {
+                    divisor *= x - *root;
+                }
+                ret += num * divisor.inv();
+            }
+            let check_num = tot[combo_count] - combo_u[combo_count][0];
+            let check_div = x - check_root;
+            ret += check_num * check_div.inv();
+            Ok(ret)
+        },
+    )?;
+    iop.verify_complete()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{total_query_size, VerificationError};
+
+    #[test]
+    fn query_size_overflow() {
+        assert_eq!(total_query_size(50, [10, 20]).unwrap(), 1500);
+        assert!(matches!(
+            total_query_size(usize::MAX, [10, 20]),
+            Err(VerificationError::InvalidProofParams)
+        ));
+        assert!(matches!(
+            total_query_size(1, [usize::MAX, 1]),
+            Err(VerificationError::InvalidProofParams)
+        ));
+    }
 }
@@ -14,11 +14,14 @@
 
 use rand::{Error, RngCore};
 
-use crate::core::{
-    fp::Fp,
-    fp4::Fp4,
-    sha::{Digest, Sha, DIGEST_WORDS},
-    sha_rng::ShaRng,
+use crate::{
+    core::{
+        fp::Fp,
+        fp4::Fp4,
+        sha::{Digest, Sha, DIGEST_WORDS},
+        sha_rng::ShaRng,
+    },
+    verify::VerificationError,
 };
 
 #[derive(Debug)]
@@ -41,45 +44,67 @@ impl<'a, S: Sha> ReadIOP<'a, S> {
         &self.sha
     }
 
-    pub fn read_u32s(&mut self, x: &mut [u32]) {
-        for i in 0..x.len() {
-            x[i] = self.proof[i];
+    /// Takes the next `size` words from the proof, failing if the proof is
+    /// too short.
+    fn take(&mut self, size: usize) -> Result<&'a [u32], VerificationError> {
+        if self.proof.len() < size {
+            return Err(VerificationError::UnexpectedEndOfSeal);
         }
-        self.proof = &self.proof[x.len()..];
+        let (head, tail) = self.proof.split_at(size);
+        self.proof = tail;
+        Ok(head)
+    }
+
+    pub fn read_u32s(&mut self, x: &mut [u32]) -> Result<(), VerificationError> {
+        x.copy_from_slice(self.take(x.len())?);
+        Ok(())
     }
 
-    pub fn read_fps(&mut self, x: &mut [Fp]) {
+    pub fn read_fps(&mut self, x: &mut [Fp]) -> Result<(), VerificationError> {
+        let words = self.take(x.len())?;
         for i in 0..x.len() {
-            x[i] = Fp::from(self.proof[i]);
+            x[i] = Fp::from(words[i]);
         }
-        self.proof = &self.proof[x.len()..];
+        Ok(())
     }
 
-    pub fn read_fp4s(&mut self, x: &mut [Fp4]) {
+    pub fn read_fp4s(&mut self, x: &mut [Fp4]) -> Result<(), VerificationError> {
+        let words = self.take(4 * x.len())?;
         for i in 0..x.len() {
             x[i] = Fp4::new(
-                Fp::from(self.proof[4 * i + 0]),
-                Fp::from(self.proof[4 * i + 1]),
-                Fp::from(self.proof[4 * i + 2]),
-                Fp::from(self.proof[4 * i + 3]),
+                Fp::from(words[4 * i + 0]),
+                Fp::from(words[4 * i + 1]),
+                Fp::from(words[4 * i + 2]),
+                Fp::from(words[4 * i + 3]),
             )
         }
-        self.proof = &self.proof[4 * x.len()..];
+        Ok(())
     }
 
-    pub fn read_digests(&mut self, x: &mut [Digest]) {
+    pub fn read_digests(&mut self, x: &mut [Digest]) -> Result<(), VerificationError> {
+        let words = self.take(DIGEST_WORDS * x.len())?;
         for i in 0..x.len() {
-            x[i] = Digest::from_slice(&self.proof[DIGEST_WORDS * i..DIGEST_WORDS * (i + 1)]);
+            x[i] = Digest::from_slice(&words[DIGEST_WORDS * i..DIGEST_WORDS * (i + 1)]);
         }
-        self.proof = &self.proof[DIGEST_WORDS * x.len()..];
+        Ok(())
+    }
+
+    /// Returns the number of words left to read from the proof.
+    pub fn remaining(&self) -> usize {
+        self.proof.len()
     }
 
     pub fn commit(&mut self, digest: &Digest) {
         self.rng.mix(digest);
     }
 
-    pub fn verify_complete(&self) {
-        assert_eq!(self.proof.len(), 0);
+    pub fn verify_complete(&self) -> Result<(), VerificationError> {
+        if self.proof.len() != 0 {
+            return Err(VerificationError::TrailingSealData {
+                words: self.proof.len(),
+            });
+        }
+        Ok(())
     }
 }
 
@@ -100,3 +125,33 @@ impl<'a, S: Sha> RngCore for ReadIOP<'a, S> {
         self.rng.try_fill_bytes(dest)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ReadIOP;
+    use crate::{
+        core::{fp::Fp, sha::default_implementation},
+        verify::VerificationError,
+    };
+
+    #[test]
+    fn underrun() {
+        let sha = default_implementation();
+        let proof = [1, 2, 3];
+        let mut iop = ReadIOP::new(sha, &proof);
+        let mut fps = [Fp::default(); 2];
+        iop.read_fps(&mut fps).unwrap();
+        assert_eq!(fps, [Fp::new(1), Fp::new(2)]);
+        assert!(matches!(
+            iop.read_fps(&mut fps),
+            Err(VerificationError::UnexpectedEndOfSeal)
+        ));
+        assert!(matches!(
+            iop.verify_complete(),
+            Err(VerificationError::TrailingSealData { words: 1 })
+        ));
+        let mut word = [0];
+        iop.read_u32s(&mut word).unwrap();
+        assert!(iop.verify_complete().is_ok());
+    }
+}
@@ -140,7 +140,7 @@ std::vector<uint32_t> ExecState::getRegisters() {
 
 void ExecState::expand() {
   if (context.numSteps == maxSteps) {
-    throw std::runtime_error("Expand failed: at max steps");
+    throw ProverError(ProverErrorCode::kCycleLimitExceeded, "Expand failed: at max steps");
   }
   LOG(1, "EXPANDING!");
   {
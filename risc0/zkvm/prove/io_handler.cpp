@@ -12,8 +12,6 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#include <sstream>
-
 #include "risc0/core/align.h"
 #include "risc0/core/log.h"
 #include "risc0/zkp/core/sha256.h"
@@ -135,7 +133,7 @@ void MemoryHandler::onWrite(MemoryState& mem, uint32_t cycle, uint32_t addr, uin
                                                                          << " bytes");
       size_t aligned_len = align(result.size());
       if ((cur_host_to_guest_offset + sizeof(uint32_t) + aligned_len) >= kMemInputEnd) {
-        throw(std::runtime_error("Read buffer overrun"));
+        throw ProverError(ProverErrorCode::kInputOverrun, "Read buffer overrun");
       }
       mem.store(cur_host_to_guest_offset, result.size());
       cur_host_to_guest_offset += sizeof(uint32_t);
@@ -178,9 +176,7 @@ uint8_t MemoryState::loadByte(uint32_t addr) {
 uint32_t MemoryState::load(uint32_t addr) {
   auto it = data.find(addr / 4);
   if (it == data.end()) {
-    std::stringstream ss;
-    ss << "addr out of range: " << hex(addr);
-    throw std::out_of_range(ss.str());
+    throw ProverError(ProverErrorCode::kMemoryFault, "addr out of range: " + hex(addr), addr);
   }
   return it->second;
 }
@@ -217,7 +213,7 @@ void MemoryState::store(uint32_t addr, const void* ptr, uint32_t len) {
 
 void MemoryState::store(uint32_t addr, uint32_t value) {
   if (addr % 4 != 0) {
-    throw std::runtime_error("Unaligned store");
+    throw ProverError(ProverErrorCode::kUnalignedAccess, "Unaligned store at " + hex(addr), addr);
   }
   uint32_t key = addr / 4;
   auto it = data.find(key);
@@ -225,7 +221,8 @@ void MemoryState::store(uint32_t addr, uint32_t value) {
     auto txn = history.lower_bound({key, 0, 0, 0});
     if (txn != history.end() && txn->addr == key && it->second != value) {
       // The guest has actually touched this memory, and we are not writing the same value
-      throw std::runtime_error("Host cannot mutate existing memory.");
+      throw ProverError(
+          ProverErrorCode::kHostMemoryConflict, "Host cannot mutate existing memory at " + hex(addr), addr);
     }
     it->second = value;
   } else {
@@ -21,6 +21,8 @@
 
 #include <map>
 #include <set>
+#include <stdexcept>
+#include <string>
 #include <vector>
 
 namespace risc0 {
@@ -28,6 +30,30 @@ namespace risc0 {
 using BufferU8 = std::vector<uint8_t>;
 using BufferU32 = std::vector<uint32_t>;
 
+// Identifies the errors in the execution of a guest that a host may want to handle.
+// These values are passed across the C API and must match the Rust ProverError codes.
+enum class ProverErrorCode : uint32_t {
+  kNone = 0,
+  kGuestPanic = 1,
+  kMemoryFault = 2,
+  kUnalignedAccess = 3,
+  kDoubleWrite = 4,
+  kCycleLimitExceeded = 5,
+  kUnknownChannel = 6,
+  kInputOverrun = 7,
+  kHostMemoryConflict = 8,
+};
+
+// An error in the execution of a guest. The argument is the address or channel the error
+// is about, if any.
+struct ProverError : public std::runtime_error {
+  ProverError(ProverErrorCode code, const std::string& what, uint32_t arg = 0)
+      : std::runtime_error(what), code(code), arg(arg) {}
+
+  ProverErrorCode code;
+  uint32_t arg;
+};
+
 struct MemoryEvent {
   uint32_t addr;
   uint32_t cycle;
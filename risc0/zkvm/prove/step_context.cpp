@@ -108,7 +108,9 @@ void StepContext::memWrite(Fp cycle, Fp addrp, Fp low, Fp high) {
     mem.data.insert({addr, data});
   } else {
     if (it->second != data && !doWrite) {
-      throw(std::runtime_error("Double wrote write-once memory at " + hex(addr * 4, 8)));
+      throw ProverError(ProverErrorCode::kDoubleWrite,
+                        "Double wrote write-once memory at " + hex(addr * 4, 8),
+                        addr * 4);
     }
     it->second = data;
   }
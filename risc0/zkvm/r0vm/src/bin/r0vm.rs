@@ -16,7 +16,10 @@ use clap::Parser;
 use std::default::Default;
 use std::{fs, io::Write};
 
-use risc0_zkvm::host::{MethodId, Prover, ProverOpts, Receipt, DEFAULT_METHOD_ID_LIMIT};
+use risc0_zkvm::host::{
+    Backend, GdbServer, MethodId, Profiler, Prover, ProverOpts, Receipt, DEFAULT_METHOD_ID_LIMIT,
+};
+use risc0_zkvm::method_id::{MethodIdList, MAX_CODE_DIGEST_COUNT};
 
 /// Generates a MethodID for a given RISC-V ELF binary.
 #[derive(Parser)]
@@ -26,7 +29,9 @@ struct Args {
     #[clap(long)]
     elf: String,
 
-    /// MethodID file; created if needed and it doesn't exist.
+    /// MethodID file; created if needed and it doesn't exist. Files ending in
+    /// `.json` hold a list of hex digests along with the limit, others hold the
+    /// raw MethodID.
     #[clap(long)]
     method_id: Option<String>,
 
@@ -44,6 +49,17 @@ struct Args {
     #[clap(long)]
     initial_input: Option<String>,
 
+    /// Profile the guest with the Rust prover, writing the cycles spent in
+    /// each call stack to this file as folded stacks for flamegraph tools.
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Wait for GDB to connect to this local TCP port, with `target remote
+    /// localhost:<port>`, and run the guest under it with the Rust prover.
+    /// Implies --skip-seal.
+    #[clap(long)]
+    gdb: Option<u16>,
+
     /// Display verbose output.
     #[clap(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -53,10 +69,38 @@ struct Args {
     limit: u32,
 }
 
+fn is_json(method_id_file: &str) -> bool {
+    method_id_file.ends_with(".json")
+}
+
+fn decode_method_id(method_id_file: &str, contents: &[u8], limit: u32) -> Option<MethodId> {
+    if !is_json(method_id_file) {
+        return MethodId::from_slice(contents).ok();
+    }
+    let list: MethodIdList = serde_json::from_slice(contents).ok()?;
+    if list.limit != limit.min(MAX_CODE_DIGEST_COUNT) {
+        return None;
+    }
+    let method_id = risc0_zkvm::method_id::MethodId::from_list(&list).ok()?;
+    MethodId::from_slice(method_id.as_slice().ok()?).ok()
+}
+
+fn encode_method_id(method_id_file: &str, method_id: &MethodId) -> Vec<u8> {
+    let bytes = method_id.as_slice().unwrap();
+    if !is_json(method_id_file) {
+        return bytes.to_vec();
+    }
+    let list = risc0_zkvm::method_id::MethodId::from_slice(bytes)
+        .unwrap()
+        .to_list();
+    serde_json::to_vec_pretty(&list).unwrap()
+}
+
 fn read_method_id(
     verbose: u8,
     elf_file: &str,
     method_id_file: &Option<String>,
+    limit: u32,
 ) -> Option<MethodId> {
     let elf_mtime = fs::metadata(elf_file).ok()?.modified().ok()?;
     let id_mtime = fs::metadata(method_id_file.as_ref()?)
@@ -68,10 +112,13 @@ fn read_method_id(
         return None;
     }
 
-    let id = MethodId::from_slice(&fs::read(method_id_file.as_ref()?).ok()?).ok()?;
-
-    // TODO(nils): Check to make sure the limit is the same as the one
-    // that was saved.
+    // Only the JSON encoding records the limit the MethodID was computed with.
+    let method_id_file_name = method_id_file.as_ref()?;
+    let id = decode_method_id(
+        method_id_file_name,
+        &fs::read(method_id_file_name).ok()?,
+        limit,
+    )?;
 
     if verbose > 0 {
         println!(
@@ -97,18 +144,20 @@ fn main() {
         );
     }
 
-    let method_id: MethodId = if args.receipt.is_none() || args.skip_seal {
+    let skip_seal = args.skip_seal || args.gdb.is_some();
+    let method_id: MethodId = if args.receipt.is_none() || skip_seal {
         // No need to generate a method ID since we don't need to
         // generate an actual proof.
         MethodId::from_slice(&[]).unwrap()
     } else {
-        read_method_id(args.verbose, &args.elf, &args.method_id).unwrap_or_else(|| {
+        read_method_id(args.verbose, &args.elf, &args.method_id, args.limit).unwrap_or_else(|| {
             if args.verbose > 0 {
                 eprintln!("Computing method id");
             }
             let computed = MethodId::compute_with_limit(&elf_contents, args.limit).unwrap();
             if let Some(method_id_file) = args.method_id {
-                std::fs::write(&method_id_file, computed.as_slice().unwrap()).unwrap();
+                let contents = encode_method_id(&method_id_file, &computed);
+                std::fs::write(&method_id_file, contents).unwrap();
                 if args.verbose > 0 {
                     eprintln!("Saved method id to {}", method_id_file);
                 }
@@ -117,8 +166,21 @@ fn main() {
         })
     };
 
-    let opts: ProverOpts =
-        ProverOpts::default().with_skip_seal(args.skip_seal || args.receipt.is_none());
+    let mut profiler = args
+        .profile
+        .as_ref()
+        .map(|_| Profiler::new(&elf_contents).unwrap());
+
+    let mut opts: ProverOpts =
+        ProverOpts::default().with_skip_seal(skip_seal || args.receipt.is_none());
+    if let Some(profiler) = &mut profiler {
+        opts = opts.with_backend(Backend::Rust).with_trace_sink(profiler);
+    }
+    if let Some(port) = args.gdb {
+        eprintln!("Waiting for GDB to connect to localhost:{}", port);
+        let server = GdbServer::listen(port).unwrap();
+        opts = opts.with_backend(Backend::Rust).with_gdb_server(server);
+    }
 
     let mut prover =
         Prover::new_with_opts(&elf_contents, method_id.as_slice().unwrap(), opts).unwrap();
@@ -127,13 +189,17 @@ fn main() {
         if args.verbose > 0 {
             eprintln!("Supplying {} bytes of initial input", input_bytes.len());
         }
-        prover.add_input_u8_slice(&input_bytes);
+        prover.add_input_u8_slice(&input_bytes).unwrap();
     }
 
     let receipt: Receipt = prover.run().unwrap();
-    let receipt_data = risc0_zkvm::serde::to_vec(&receipt).unwrap();
+    let method_id_data = method_id.as_slice().unwrap();
+    let receipt_data = receipt
+        .to_envelope((!method_id_data.is_empty()).then_some(method_id_data))
+        .unwrap()
+        .to_bytes();
 
-    if args.skip_seal || args.receipt.is_none() {
+    if skip_seal || args.receipt.is_none() {
         if args.verbose > 0 {
             eprintln!("Skipping seal generation.");
         }
@@ -144,7 +210,7 @@ fn main() {
         }
     }
     if let Some(receipt_file) = args.receipt {
-        fs::write(&receipt_file, bytemuck::cast_slice(&receipt_data)).unwrap();
+        fs::write(&receipt_file, &receipt_data).unwrap();
         if args.verbose > 0 {
             eprintln!(
                 "Wrote {} bytes of receipt to {}",
@@ -158,4 +224,15 @@ fn main() {
         eprintln!("Writing {} bytes of output to stdout", output.len());
     }
     std::io::stdout().write_all(output).unwrap();
+    drop(prover);
+
+    if let (Some(profile_file), Some(profiler)) = (args.profile, profiler) {
+        let file = fs::File::create(&profile_file).unwrap();
+        profiler
+            .write_folded(std::io::BufWriter::new(file))
+            .unwrap();
+        if args.verbose > 0 {
+            eprintln!("Wrote profile to {}", profile_file);
+        }
+    }
 }
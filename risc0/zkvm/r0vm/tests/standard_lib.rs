@@ -4,7 +4,7 @@ use anyhow::Result;
 use assert_cmd::Command;
 use assert_fs::{fixture::PathChild, TempDir};
 
-use risc0_zkvm::host::Receipt;
+use risc0_zkvm::{host::Receipt, method_id::MethodId};
 
 static EXPECTED_STDOUT: &str = "Hello world on stdout!\n";
 static EXPECTED_STDERR: &str = "Hello world on stderr!\n";
@@ -30,13 +30,7 @@ fn stdio_outputs() -> Result<()> {
 }
 
 fn load_receipt(p: &Path) -> Receipt {
-    const WORD_SIZE: usize = std::mem::size_of::<u32>();
-    let data = std::fs::read(p).unwrap();
-    let as_u32: Vec<u32> = data
-        .chunks(WORD_SIZE)
-        .map(|bytes| u32::from_le_bytes(<[u8; WORD_SIZE]>::try_from(bytes).unwrap()))
-        .collect();
-    risc0_zkvm::serde::from_slice(&as_u32).unwrap()
+    Receipt::from_bytes(&std::fs::read(p).unwrap()).unwrap()
 }
 
 #[test]
@@ -66,6 +60,37 @@ fn stdio_outputs_in_receipt() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn stdio_outputs_in_receipt_with_json_method_id() -> Result<()> {
+    let temp = TempDir::new().unwrap();
+    let receipt_file = temp.child("receipt.dat");
+    let method_id_file = temp.child("method_id.json");
+    let method_id = MethodId::from_slice(risc0_zkvm_methods::STANDARD_LIB_ID).unwrap();
+    std::fs::write(
+        &method_id_file,
+        serde_json::to_vec(&method_id.to_list()).unwrap(),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("r0vm")?;
+
+    cmd.arg("--elf")
+        .arg(risc0_zkvm_methods::STANDARD_LIB_PATH)
+        .arg("--method-id")
+        .arg(&*method_id_file)
+        .arg("--receipt")
+        .arg(&*receipt_file);
+    cmd.assert()
+        .stderr(EXPECTED_STDERR)
+        .stdout(EXPECTED_STDOUT)
+        .success();
+
+    let receipt = load_receipt(&receipt_file);
+    receipt.verify(risc0_zkvm_methods::STANDARD_LIB_ID).unwrap();
+
+    Ok(())
+}
+
 #[test]
 fn stdio_outputs_in_receipt_without_seal() -> Result<()> {
     let temp = TempDir::new().unwrap();
@@ -93,3 +118,25 @@ fn stdio_outputs_in_receipt_without_seal() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn profile() -> Result<()> {
+    let temp = TempDir::new().unwrap();
+    let profile_file = temp.child("profile.folded");
+
+    let mut cmd = Command::cargo_bin("r0vm")?;
+    cmd.arg("--elf")
+        .arg(risc0_zkvm_methods::STANDARD_LIB_PATH)
+        .arg("--profile")
+        .arg(&*profile_file);
+    cmd.assert().stdout(EXPECTED_STDOUT).success();
+
+    let profile = std::fs::read_to_string(&profile_file).unwrap();
+    assert!(profile.lines().count() > 0);
+    for line in profile.lines() {
+        let (_, cycles) = line.rsplit_once(' ').unwrap();
+        cycles.parse::<u64>().unwrap();
+    }
+
+    Ok(())
+}
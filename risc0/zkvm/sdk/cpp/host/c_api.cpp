@@ -36,8 +36,10 @@ struct risc0_u8buffer {
 
 template <typename F> void ffi_wrap_void(risc0_error* err, F fn) {
   try {
-    err->msg = nullptr;
+    *err = risc0_error{nullptr, 0, 0};
     fn();
+  } catch (const risc0::ProverError& ex) {
+    *err = risc0_error{new risc0_string{ex.what()}, static_cast<uint32_t>(ex.code), ex.arg};
   } catch (const std::exception& ex) {
     err->msg = new risc0_string{ex.what()};
   }
@@ -45,8 +47,11 @@ template <typename F> void ffi_wrap_void(risc0_error* err, F fn) {
 
 template <typename T, typename F> T ffi_wrap(risc0_error* err, T val, F fn) {
   try {
-    err->msg = nullptr;
+    *err = risc0_error{nullptr, 0, 0};
     return fn();
+  } catch (const risc0::ProverError& ex) {
+    *err = risc0_error{new risc0_string{ex.what()}, static_cast<uint32_t>(ex.code), ex.arg};
+    return val;
   } catch (const std::exception& ex) {
     err->msg = new risc0_string{ex.what()};
     return val;
@@ -40,8 +40,13 @@ typedef struct risc0_u8buffer risc0_u8buffer;
 // Error
 //
 
+// When a call fails, msg is set to the message of the error. If the error is one of the
+// execution errors of the prover, code identifies it (see risc0::ProverErrorCode) and arg
+// holds its address or channel. Otherwise, code is zero.
 typedef struct {
   risc0_string* msg;
+  uint32_t code;
+  uint32_t arg;
 } risc0_error;
 
 //
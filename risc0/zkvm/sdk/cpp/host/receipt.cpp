@@ -98,11 +98,16 @@ struct Prover::Impl : public IoHandler {
   BufferU8 onSendRecv(uint32_t channel, const BufferU8& buf) override {
     auto it = sendRecvHandlers.find(channel);
     if (it == sendRecvHandlers.end()) {
-      throw(std::runtime_error("Unknown channel " + std::to_string(channel)));
+      throw ProverError(
+          ProverErrorCode::kUnknownChannel, "Unknown channel " + std::to_string(channel), channel);
     }
     return it->second(channel, buf);
   }
 
+  void onFault(const std::string& msg) override {
+    throw ProverError(ProverErrorCode::kGuestPanic, msg);
+  }
+
   void onCommit(const BufferU8& buf) override {
     LOG(1, "IoHandler::onCommit> " << buf.size());
     commitBuffer.insert(commitBuffer.end(), buf.begin(), buf.end());
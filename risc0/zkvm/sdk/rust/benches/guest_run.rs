@@ -17,7 +17,9 @@
 //! for the guest, as opposed to performance data on the prover As
 //! such, they do not generate seals, and these performance numbers
 //! are not indicitive of performance with cryptographically secure
-//! proofs.
+//! proofs. The guests run in the circuit executor of the Rust prover, as
+//! [Prover::trace] does, so the timings include the memory transactions it
+//! logs for the proof.
 
 use criterion::{
     black_box, criterion_group, criterion_main, Bencher, BenchmarkId, Criterion, SamplingMode,
@@ -29,7 +31,7 @@ use rand::{
 };
 use std::time::{Duration, Instant};
 
-use risc0_zkvm::host::{Prover, ProverOpts};
+use risc0_zkvm::host::{Backend, Prover, ProverOpts};
 use risc0_zkvm::serde::to_vec;
 use risc0_zkvm_methods::{
     bench::{BenchmarkSpec, SpecWithIters},
@@ -42,13 +44,13 @@ fn run_guest(spec: SpecWithIters) -> Duration {
     let mut prover = Prover::new_with_opts(
         &std::fs::read(BENCH_PATH).unwrap(),
         BENCH_ID,
-        ProverOpts::default().with_skip_seal(true),
+        ProverOpts::default().with_backend(Backend::Rust),
     )
     .unwrap();
-    prover.add_input(input_data.as_slice()).unwrap();
+    prover.add_input_u32_slice(input_data.as_slice()).unwrap();
 
     let start = Instant::now();
-    black_box(prover.run().unwrap());
+    black_box(prover.trace().unwrap());
     start.elapsed()
 }
 
@@ -87,6 +89,7 @@ pub fn bench(c: &mut Criterion) {
     let mut memset_group = c.benchmark_group("memset");
     memset_group.sampling_mode(SamplingMode::Flat);
     for buf_bytes in [32u64, 64, 128, 256, 512, 1024, 2048, 4096] {
+        memset_group.throughput(Throughput::Bytes(buf_bytes));
         memset_group.bench_with_input(
             BenchmarkId::new("memset", buf_bytes),
             &buf_bytes,
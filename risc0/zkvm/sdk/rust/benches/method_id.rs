@@ -0,0 +1,36 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `method_id' tracks how long it takes to compute the [MethodId] of a
+//! method for different limits.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use risc0_zkvm::method_id::{MethodId, DEFAULT_METHOD_ID_LIMIT};
+use risc0_zkvm_methods::IO_PATH;
+
+pub fn bench(c: &mut Criterion) {
+    let elf = std::fs::read(IO_PATH).unwrap();
+    let mut group = c.benchmark_group("method_id");
+    group.sample_size(10);
+    for limit in [4, 8, DEFAULT_METHOD_ID_LIMIT] {
+        group.bench_with_input(BenchmarkId::from_parameter(limit), &limit, |b, &limit| {
+            b.iter(|| MethodId::compute_with_limit(&elf, limit).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
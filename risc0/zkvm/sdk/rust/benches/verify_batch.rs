@@ -0,0 +1,73 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `verify_batch' compares verifying many receipts for the same method
+//! with [BatchVerifier] against calling [Receipt::verify] on each one.
+//! It requires the `parallel` feature, so that the batch is verified on the
+//! rayon thread pool: `cargo bench --features parallel --bench verify_batch`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use risc0_zkvm::host::{Backend, Prover, ProverOpts};
+use risc0_zkvm::receipt::Receipt;
+use risc0_zkvm::serde::to_vec;
+use risc0_zkvm::verify::BatchVerifier;
+use risc0_zkvm_methods::{SHA_ID, SHA_PATH};
+
+fn prove_receipts(count: usize) -> Vec<Receipt> {
+    let elf = std::fs::read(SHA_PATH).unwrap();
+    (0..count)
+        .map(|i| {
+            let opts = ProverOpts::default().with_backend(Backend::Rust);
+            let mut prover = Prover::new_with_opts(&elf, SHA_ID, opts).unwrap();
+            prover
+                .add_input_u32_slice(to_vec(&format!("input {}", i)).unwrap().as_slice())
+                .unwrap();
+            let receipt = prover.run().unwrap();
+            Receipt::new(receipt.get_journal().unwrap(), receipt.get_seal().unwrap()).unwrap()
+        })
+        .collect()
+}
+
+pub fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_batch");
+    group.sample_size(10);
+    for count in [1, 4, 16] {
+        let receipts = prove_receipts(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("loop", count), &receipts, |b, receipts| {
+            b.iter(|| {
+                for receipt in receipts {
+                    black_box(receipt.verify(SHA_ID).unwrap());
+                }
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("batch", count),
+            &receipts,
+            |b, receipts| {
+                let verifier = BatchVerifier::new(SHA_ID).unwrap();
+                b.iter(|| {
+                    for result in verifier.verify_all(receipts) {
+                        black_box(result.unwrap());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -26,7 +26,7 @@ use std::{
 };
 
 use cargo_metadata::{MetadataCommand, Package};
-use risc0_zkvm::host::{MethodId, DEFAULT_METHOD_ID_LIMIT};
+use risc0_zkvm::method_id::{MethodDigest, MethodId, DEFAULT_METHOD_ID_LIMIT};
 use risc0_zkvm_platform_sys::LINKER_SCRIPT;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
@@ -62,38 +62,33 @@ impl Risc0Method {
             std::process::exit(-1);
         }
 
-        // Method ID calculation is slow, so only recalculate it if we
-        // actually get a different ELF file.
-        let method_id_path = self.elf_path.with_extension("id");
-        let elf_sha_path = self.elf_path.with_extension("sha");
+        // Method ID calculation is slow, so the result is cached next to the
+        // ELF and only recalculated if we actually get a different ELF file.
         let elf_contents = std::fs::read(&self.elf_path).unwrap();
-        let (elf_sha, elf_sha_hex) = sha_digest_with_hex(&elf_contents);
-        if method_id_path.exists() {
-            if let Ok(cached_sha) = std::fs::read(&elf_sha_path) {
-                if cached_sha == elf_sha.as_slice() {
-                    println!("MethodID for {} ({}) up to date", self.name, elf_sha_hex);
-                    return std::fs::read(&method_id_path).unwrap();
-                }
-            }
-        }
-
-        println!("Computing MethodID for {} ({:})!", self.name, elf_sha_hex);
-        let elf_contents = std::fs::read(&self.elf_path).unwrap();
-        let method_id = MethodId::compute_with_limit(&elf_contents, code_limit).unwrap();
-        let slice = method_id.as_slice().unwrap();
-        std::fs::write(method_id_path, slice).unwrap();
-        std::fs::write(elf_sha_path, elf_sha).unwrap();
-        Vec::from(slice)
+        let (_, elf_sha_hex) = sha_digest_with_hex(&elf_contents);
+        println!("MethodID for {} ({})", self.name, elf_sha_hex);
+        let cache_dir = self.elf_path.parent().unwrap();
+        let method_id = MethodId::compute_cached(&elf_contents, code_limit, cache_dir).unwrap();
+        Vec::from(method_id.as_slice().unwrap())
     }
 
     fn rust_def(&self, code_limit: u32) -> String {
         let elf_path = self.elf_path.display();
         let upper = self.name.to_uppercase();
         let method_id = self.make_method_id(code_limit);
+        // Only a complete table of code roots has a digest.
+        let method_digest = match MethodDigest::from_table(&method_id) {
+            Ok(digest) => format!(
+                "pub const {upper}_DIGEST: &'static [u8] = &{:?};",
+                digest.as_bytes()
+            ),
+            Err(_) => String::new(),
+        };
         format!(
             r##"
 pub const {upper}_PATH: &'static str = r#"{elf_path}"#;
 pub const {upper}_ID: &'static [u8] = &{method_id:?};
+{method_digest}
             "##
         )
     }
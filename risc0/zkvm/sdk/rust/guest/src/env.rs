@@ -24,6 +24,8 @@ use risc0_zkvm::{
 };
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "verify")]
+use crate::verify::{MethodId, Receipt, ReceiptClaim};
 use crate::{align_up, io::host_sendrecv, memory_barrier, sha};
 
 struct Env {
@@ -108,6 +110,31 @@ pub fn commit<T: Serialize>(data: &T) {
     ENV.get().commit(data);
 }
 
+/// Read a [Receipt] supplied by the host with `Prover::add_receipt`.
+#[cfg(feature = "verify")]
+pub fn read_receipt() -> Receipt {
+    Receipt {
+        journal: read(),
+        seal: read(),
+    }
+}
+
+/// Read a [Receipt] from the host, verify it against the given method ID, and
+/// commit the resulting [ReceiptClaim] to the journal. Faults if the receipt
+/// does not verify.
+#[cfg(feature = "verify")]
+pub fn verify_receipt(method_id: &MethodId) -> Receipt {
+    let receipt = read_receipt();
+    crate::verify::verify(&receipt, method_id).unwrap();
+    let claim = ReceiptClaim::new(
+        &sha::Impl {},
+        method_id.as_slice().unwrap(),
+        bytemuck::cast_slice(&receipt.journal),
+    );
+    commit(&claim);
+    receipt
+}
+
 impl Env {
     fn new() -> Self {
         Env {
@@ -34,6 +34,10 @@ pub mod sha;
 /// Functions for handling input and output
 pub mod io;
 
+/// Functions for verifying receipts within the guest.
+#[cfg(feature = "verify")]
+pub mod verify;
+
 use core::{arch::asm, mem, panic::PanicInfo, ptr};
 
 extern "C" {
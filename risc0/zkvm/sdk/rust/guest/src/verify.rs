@@ -0,0 +1,27 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub use risc0_zkvm::{
+    method_id::MethodId,
+    receipt::{IntoMethodId, Receipt, ReceiptClaim},
+};
+
+use crate::sha;
+
+/// Verifies that the given [Receipt] is a valid result of executing the
+/// method associated with the given method ID, using the zkVM's SHA
+/// accelerator for hashing.
+pub fn verify<M: IntoMethodId>(receipt: &Receipt, method_id: M) -> anyhow::Result<()> {
+    receipt.verify_with_hash(&sha::Impl {}, method_id)
+}
@@ -18,6 +18,8 @@ use anyhow::{anyhow, bail, Result};
 use xmas_elf::{
     header::{Class, Data, Machine, Version},
     program::ProgramHeader,
+    sections::{SectionData, ShType},
+    symbol_table::{self, Entry},
     ElfFile,
 };
 
@@ -84,3 +86,42 @@ impl Program {
         Ok(Program { entry, image })
     }
 }
+
+/// A function symbol of an ELF file, as returned by [load_symbols].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Returns the function symbols of an ELF file, sorted by address. A stripped
+/// file has none.
+pub fn load_symbols(input: &[u8]) -> Result<Vec<Symbol>> {
+    let elf = ElfFile::new(input).map_err(|err| anyhow!(err))?;
+    let mut symbols = Vec::new();
+    for section in elf.section_iter() {
+        if section.get_type() != Ok(ShType::SymTab) {
+            continue;
+        }
+        let entries = match section.get_data(&elf).map_err(|err| anyhow!(err))? {
+            SectionData::SymbolTable32(entries) => entries,
+            _ => bail!("Not a 32-bit symbol table"),
+        };
+        for entry in entries {
+            if entry.get_type() != Ok(symbol_table::Type::Func) {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: entry
+                    .get_name(&elf)
+                    .map_err(|err| anyhow!(err))?
+                    .to_string(),
+                addr: entry.value().try_into()?,
+                size: entry.size().try_into()?,
+            });
+        }
+    }
+    symbols.sort_by_key(|symbol| symbol.addr);
+    Ok(symbols)
+}
@@ -0,0 +1,433 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A versioned, self-describing container for receipts.
+//!
+//! An envelope is a sequence of words: a magic number, a format version, and
+//! a list of tagged sections, each prefixed with its tag and its length in
+//! words. Readers skip sections with tags they do not know, and ignore words
+//! at the end of a known section beyond the fields they understand, so newer
+//! writers can add information within the same major version.
+
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use risc0_zkp::core::sha::{Digest, DIGEST_WORDS};
+
+use crate::platform::WORD_SIZE;
+
+/// Marks the start of a receipt envelope ("R0RC" in little-endian bytes).
+pub const MAGIC: u32 = 0x43523052;
+
+/// The major format version. Readers reject envelopes with a different major
+/// version. Version 2 seals record their
+/// [ProofParams](risc0_zkp::params::ProofParams) after the circuit outputs,
+/// which version 1 readers would misparse.
+pub const VERSION_MAJOR: u16 = 2;
+
+/// The minor format version. Minor versions only add sections or fields that
+/// older readers may safely skip.
+pub const VERSION_MINOR: u16 = 0;
+
+const TAG_HEADER: u32 = 1;
+const TAG_JOURNAL: u32 = 2;
+const TAG_SEAL: u32 = 3;
+const TAG_METHOD_ID: u32 = 4;
+const TAG_BACKEND: u32 = 5;
+
+const HEADER_WORDS: usize = 2 + DIGEST_WORDS;
+
+/// Errors produced while decoding a [ReceiptEnvelope].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EnvelopeError {
+    BadMagic,
+    UnsupportedVersion { major: u16, minor: u16 },
+    UnexpectedEnd,
+    MissingSection(u32),
+    UnknownHashFunction(u32),
+    UnknownBackend(u32),
+    UnalignedBytes,
+}
+
+impl Display for EnvelopeError {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a receipt envelope"),
+            Self::UnsupportedVersion { major, minor } => write!(
+                f,
+                "Unsupported receipt envelope version {major}.{minor}, expected {VERSION_MAJOR}.x"
+            ),
+            Self::UnexpectedEnd => write!(f, "Unexpected end of receipt envelope"),
+            Self::MissingSection(tag) => write!(f, "Receipt envelope is missing section {tag}"),
+            Self::UnknownHashFunction(id) => write!(f, "Unknown hash function {id}"),
+            Self::UnknownBackend(id) => write!(f, "Unknown prover backend {id}"),
+            Self::UnalignedBytes => write!(f, "Receipt envelope is not a whole number of words"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EnvelopeError {}
+
+/// The hash function used to build the seal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HashFunction {
+    Sha256 = 1,
+}
+
+impl TryFrom<u32> for HashFunction {
+    type Error = EnvelopeError;
+
+    fn try_from(id: u32) -> Result<Self, EnvelopeError> {
+        match id {
+            1 => Ok(HashFunction::Sha256),
+            _ => Err(EnvelopeError::UnknownHashFunction(id)),
+        }
+    }
+}
+
+/// The prover that produced a seal. The two provers lay out the circuit
+/// outputs in the seal differently, so a seal can only be verified by the
+/// verifier matching the prover that produced it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backend {
+    /// The C++ prover, called through FFI.
+    Ffi = 1,
+    /// The prover written in Rust.
+    Rust = 2,
+}
+
+impl Default for Backend {
+    /// The Rust prover with the `pure-prove` feature, the FFI-based prover
+    /// otherwise.
+    fn default() -> Self {
+        if cfg!(feature = "pure-prove") {
+            Backend::Rust
+        } else {
+            Backend::Ffi
+        }
+    }
+}
+
+impl TryFrom<u32> for Backend {
+    type Error = EnvelopeError;
+
+    fn try_from(id: u32) -> Result<Self, EnvelopeError> {
+        match id {
+            1 => Ok(Backend::Ffi),
+            2 => Ok(Backend::Rust),
+            _ => Err(EnvelopeError::UnknownBackend(id)),
+        }
+    }
+}
+
+/// Describes how the seal in an envelope was produced.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReceiptHeader {
+    pub hash_fn: HashFunction,
+    /// The size of the execution trace as a power of 2.
+    pub po2: u32,
+    /// Identifies the circuit the seal was proven against, see
+    /// [circuit_digest].
+    pub circuit: Digest,
+}
+
+/// A receipt together with the metadata needed to verify it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReceiptEnvelope {
+    pub header: ReceiptHeader,
+    pub journal: Vec<u8>,
+    pub seal: Vec<u32>,
+    pub method_id: Option<Vec<u8>>,
+    /// The prover that produced the seal. If the envelope does not record it,
+    /// the default [Backend] is assumed.
+    pub backend: Option<Backend>,
+}
+
+impl ReceiptEnvelope {
+    /// Returns true if the given words start with the envelope magic number.
+    pub fn is_envelope(words: &[u32]) -> bool {
+        words.first() == Some(&MAGIC)
+    }
+
+    pub fn to_words(&self) -> Vec<u32> {
+        let mut words = Vec::new();
+        words.push(MAGIC);
+        words.push((VERSION_MAJOR as u32) << 16 | VERSION_MINOR as u32);
+
+        let mut header = Vec::with_capacity(HEADER_WORDS);
+        header.push(self.header.hash_fn as u32);
+        header.push(self.header.po2);
+        header.extend_from_slice(self.header.circuit.as_slice());
+        push_section(&mut words, TAG_HEADER, &header);
+        push_section(&mut words, TAG_JOURNAL, &bytes_to_words(&self.journal));
+        push_section(&mut words, TAG_SEAL, &self.seal);
+        if let Some(method_id) = &self.method_id {
+            push_section(&mut words, TAG_METHOD_ID, &bytes_to_words(method_id));
+        }
+        if let Some(backend) = self.backend {
+            push_section(&mut words, TAG_BACKEND, &[backend as u32]);
+        }
+        words
+    }
+
+    pub fn from_words(words: &[u32]) -> Result<Self, EnvelopeError> {
+        let (&magic, words) = words.split_first().ok_or(EnvelopeError::UnexpectedEnd)?;
+        if magic != MAGIC {
+            return Err(EnvelopeError::BadMagic);
+        }
+        let (&version, mut words) = words.split_first().ok_or(EnvelopeError::UnexpectedEnd)?;
+        let major = (version >> 16) as u16;
+        let minor = version as u16;
+        if major != VERSION_MAJOR {
+            return Err(EnvelopeError::UnsupportedVersion { major, minor });
+        }
+
+        let mut header = None;
+        let mut journal = None;
+        let mut seal = None;
+        let mut method_id = None;
+        let mut backend = None;
+        while !words.is_empty() {
+            let (tag, body, rest) = split_section(words)?;
+            words = rest;
+            match tag {
+                TAG_HEADER => {
+                    if body.len() < HEADER_WORDS {
+                        return Err(EnvelopeError::UnexpectedEnd);
+                    }
+                    header = Some(ReceiptHeader {
+                        hash_fn: body[0].try_into()?,
+                        po2: body[1],
+                        circuit: Digest::from_slice(&body[2..HEADER_WORDS]),
+                    });
+                }
+                TAG_JOURNAL => journal = Some(words_to_bytes(body)?),
+                TAG_SEAL => seal = Some(body.to_vec()),
+                TAG_METHOD_ID => method_id = Some(words_to_bytes(body)?),
+                TAG_BACKEND => {
+                    let id = body.first().ok_or(EnvelopeError::UnexpectedEnd)?;
+                    backend = Some((*id).try_into()?);
+                }
+                // Sections added by newer writers.
+                _ => {}
+            }
+        }
+
+        Ok(ReceiptEnvelope {
+            header: header.ok_or(EnvelopeError::MissingSection(TAG_HEADER))?,
+            journal: journal.ok_or(EnvelopeError::MissingSection(TAG_JOURNAL))?,
+            seal: seal.ok_or(EnvelopeError::MissingSection(TAG_SEAL))?,
+            method_id,
+            backend,
+        })
+    }
+
+    /// Encodes the envelope as little-endian bytes, suitable for writing to a
+    /// file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_words()
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        Self::from_words(&le_bytes_to_words(bytes)?)
+    }
+}
+
+/// Computes a digest of the tap layout of the zkVM circuit, which changes
+/// whenever the circuit does.
+#[cfg(feature = "circuit")]
+pub fn circuit_digest() -> Digest {
+    use risc0_zkp::{
+        adapter::{CircuitInfo, TapsProvider},
+        core::sha::{default_implementation, Sha},
+    };
+    use risc0_zkvm_circuit::CircuitImpl;
+
+    let circuit = CircuitImpl::new();
+    let taps = circuit.get_taps();
+    let mut words = Vec::new();
+    words.push(circuit.output_size() as u32);
+    words.push(circuit.mix_size() as u32);
+    for tap in taps.taps.iter() {
+        words.push(tap.group as u32);
+        words.push(tap.offset as u32);
+        words.push(tap.back as u32);
+        words.push(tap.combo as u32);
+    }
+    words.extend(taps.combo_taps.iter().map(|x| *x as u32));
+    words.extend(taps.combo_begin.iter().map(|x| *x as u32));
+    *default_implementation().hash_words(&words)
+}
+
+pub(crate) fn le_bytes_to_words(bytes: &[u8]) -> Result<Vec<u32>, EnvelopeError> {
+    let chunks = bytes.chunks_exact(WORD_SIZE);
+    if chunks.remainder().len() != 0 {
+        return Err(EnvelopeError::UnalignedBytes);
+    }
+    Ok(chunks
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect())
+}
+
+fn push_section(words: &mut Vec<u32>, tag: u32, body: &[u32]) {
+    words.push(tag);
+    words.push(body.len() as u32);
+    words.extend_from_slice(body);
+}
+
+fn split_section(words: &[u32]) -> Result<(u32, &[u32], &[u32]), EnvelopeError> {
+    if words.len() < 2 {
+        return Err(EnvelopeError::UnexpectedEnd);
+    }
+    let (tag, len) = (words[0], words[1] as usize);
+    let words = &words[2..];
+    if words.len() < len {
+        return Err(EnvelopeError::UnexpectedEnd);
+    }
+    let (body, rest) = words.split_at(len);
+    Ok((tag, body, rest))
+}
+
+// Byte strings are stored as their length in bytes followed by the bytes,
+// padded with zeros to a whole number of words.
+fn bytes_to_words(bytes: &[u8]) -> Vec<u32> {
+    let mut words = Vec::with_capacity(1 + (bytes.len() + WORD_SIZE - 1) / WORD_SIZE);
+    words.push(bytes.len() as u32);
+    for chunk in bytes.chunks(WORD_SIZE) {
+        let mut word = [0u8; WORD_SIZE];
+        word[..chunk.len()].copy_from_slice(chunk);
+        words.push(u32::from_le_bytes(word));
+    }
+    words
+}
+
+fn words_to_bytes(words: &[u32]) -> Result<Vec<u8>, EnvelopeError> {
+    let (&len, words) = words.split_first().ok_or(EnvelopeError::UnexpectedEnd)?;
+    let mut bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    if bytes.len() < len as usize {
+        return Err(EnvelopeError::UnexpectedEnd);
+    }
+    bytes.truncate(len as usize);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn envelope() -> ReceiptEnvelope {
+        ReceiptEnvelope {
+            header: ReceiptHeader {
+                hash_fn: HashFunction::Sha256,
+                po2: 14,
+                circuit: Digest::new([1, 2, 3, 4, 5, 6, 7, 8]),
+            },
+            journal: vec![1, 2, 3, 4, 5],
+            seal: vec![10, 11, 12],
+            method_id: Some(vec![0xaa; 64]),
+            backend: Some(Backend::Rust),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let envelope = envelope();
+        let words = envelope.to_words();
+        assert!(ReceiptEnvelope::is_envelope(&words));
+        assert_eq!(ReceiptEnvelope::from_words(&words).unwrap(), envelope);
+        let bytes = envelope.to_bytes();
+        assert_eq!(&bytes[..4], b"R0RC");
+        assert_eq!(ReceiptEnvelope::from_bytes(&bytes).unwrap(), envelope);
+
+        let envelope = ReceiptEnvelope {
+            method_id: None,
+            backend: None,
+            ..envelope
+        };
+        assert_eq!(
+            ReceiptEnvelope::from_words(&envelope.to_words()).unwrap(),
+            envelope
+        );
+    }
+
+    #[test]
+    fn forward_compatible() {
+        let envelope = envelope();
+        let mut words = envelope.to_words();
+        // A newer minor version, an unknown section, and an extended header.
+        words[1] += 1;
+        words.extend_from_slice(&[99, 2, 0xdead, 0xbeef]);
+        words[3] += 1;
+        words.insert(4 + HEADER_WORDS, 0x1234);
+        assert_eq!(ReceiptEnvelope::from_words(&words).unwrap(), envelope);
+    }
+
+    #[test]
+    fn errors() {
+        let words = envelope().to_words();
+        assert_eq!(
+            ReceiptEnvelope::from_words(&words[1..]),
+            Err(EnvelopeError::BadMagic)
+        );
+
+        let mut newer = words.clone();
+        newer[1] = 3 << 16;
+        assert_eq!(
+            ReceiptEnvelope::from_words(&newer),
+            Err(EnvelopeError::UnsupportedVersion { major: 3, minor: 0 })
+        );
+
+        // Version 1 seals have no proof parameters.
+        let mut older = words.clone();
+        older[1] = 1 << 16 | 1;
+        assert_eq!(
+            ReceiptEnvelope::from_words(&older),
+            Err(EnvelopeError::UnsupportedVersion { major: 1, minor: 1 })
+        );
+
+        assert_eq!(
+            ReceiptEnvelope::from_words(&words[..words.len() - 1]),
+            Err(EnvelopeError::UnexpectedEnd)
+        );
+
+        let mut unknown_hash = words.clone();
+        unknown_hash[4] = 7;
+        assert_eq!(
+            ReceiptEnvelope::from_words(&unknown_hash),
+            Err(EnvelopeError::UnknownHashFunction(7))
+        );
+
+        let mut unknown_backend = words.clone();
+        let last = unknown_backend.len() - 1;
+        unknown_backend[last] = 7;
+        assert_eq!(
+            ReceiptEnvelope::from_words(&unknown_backend),
+            Err(EnvelopeError::UnknownBackend(7))
+        );
+
+        assert_eq!(
+            ReceiptEnvelope::from_words(&words[..2 + 2 + HEADER_WORDS]),
+            Err(EnvelopeError::MissingSection(TAG_JOURNAL))
+        );
+
+        assert_eq!(
+            ReceiptEnvelope::from_bytes(&[0x52, 0x30, 0x52]),
+            Err(EnvelopeError::UnalignedBytes)
+        );
+    }
+}
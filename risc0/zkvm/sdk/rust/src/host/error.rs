@@ -0,0 +1,237 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fmt::{self, Display},
+    panic::{self, AssertUnwindSafe},
+};
+
+use super::Exception;
+
+/// An error in the execution of a guest by the [Prover](crate::host::Prover).
+///
+/// The Rust prover returns it inside its [anyhow::Error], and the FFI-based
+/// prover inside its [Exception]. Use [ProverError::find] to get it from
+/// either.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProverError {
+    /// The guest panicked or otherwise faulted, with the given message.
+    GuestPanic(String),
+    /// The guest made the host read memory at an address that was never
+    /// loaded or written.
+    MemoryFault {
+        /// The byte address of the access.
+        addr: u32,
+        /// The cycle of the access, if known.
+        cycle: Option<u32>,
+    },
+    /// A word of memory was accessed at an address that is not word-aligned.
+    UnalignedAccess {
+        /// The byte address of the access.
+        addr: u32,
+        /// The cycle of the access, if known.
+        cycle: Option<u32>,
+    },
+    /// Write-once memory was written twice with different values.
+    DoubleWrite {
+        /// The byte address of the write.
+        addr: u32,
+        /// The cycle of the write, if known.
+        cycle: Option<u32>,
+    },
+    /// The execution needs more cycles than the largest trace the prover
+    /// supports.
+    CycleLimitExceeded,
+    /// The guest used a sendrecv channel that has no handler.
+    UnknownChannel(u32),
+    /// The reply to a sendrecv request does not fit in the guest's input
+    /// region.
+    InputOverrun,
+    /// The host tried to overwrite memory the guest has already used, for
+    /// example with the reply to a sendrecv request.
+    HostMemoryConflict {
+        /// The byte address of the write.
+        addr: u32,
+    },
+    /// A sendrecv callback or [SendRecvHandler](super::SendRecvHandler)
+    /// panicked or returned an error.
+    CallbackFailed {
+        /// The channel of the callback.
+        channel: u32,
+        /// The panic or error message.
+        msg: String,
+    },
+}
+
+impl ProverError {
+    /// Returns the [ProverError] behind an error returned by either prover,
+    /// if there is one.
+    pub fn find(err: &anyhow::Error) -> Option<ProverError> {
+        if let Some(err) = err.downcast_ref::<ProverError>() {
+            return Some(err.clone());
+        }
+        err.downcast_ref::<Exception>()
+            .and_then(|err| err.prover_error().cloned())
+    }
+
+    /// Sets the cycle at which a memory error happened, if not already known.
+    pub(crate) fn at_cycle(mut self, at: u32) -> Self {
+        match &mut self {
+            ProverError::MemoryFault { cycle, .. }
+            | ProverError::UnalignedAccess { cycle, .. }
+            | ProverError::DoubleWrite { cycle, .. } => {
+                cycle.get_or_insert(at);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Calls the sendrecv handler for `channel`, turning a panic or an error
+    /// into [ProverError::CallbackFailed].
+    pub(crate) fn catch_callback<F>(channel: u32, callback: F) -> Result<Vec<u8>, ProverError>
+    where
+        F: FnOnce() -> anyhow::Result<Vec<u8>>,
+    {
+        match panic::catch_unwind(AssertUnwindSafe(callback)) {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(err)) => Err(ProverError::CallbackFailed {
+                channel,
+                msg: format!("{err:#}"),
+            }),
+            Err(payload) => {
+                let msg = match payload.downcast::<String>() {
+                    Ok(msg) => *msg,
+                    Err(payload) => match payload.downcast::<&str>() {
+                        Ok(msg) => msg.to_string(),
+                        Err(_) => "unknown panic".to_string(),
+                    },
+                };
+                Err(ProverError::CallbackFailed { channel, msg })
+            }
+        }
+    }
+
+    /// Returns the error identified by a `risc0::ProverErrorCode` of the C++
+    /// prover, with the address or channel in `arg`. For a guest panic, `msg`
+    /// is the message of the guest.
+    pub(crate) fn from_code(code: u32, arg: u32, msg: &str) -> Option<ProverError> {
+        match code {
+            1 => Some(ProverError::GuestPanic(msg.to_string())),
+            2 => Some(ProverError::MemoryFault {
+                addr: arg,
+                cycle: None,
+            }),
+            3 => Some(ProverError::UnalignedAccess {
+                addr: arg,
+                cycle: None,
+            }),
+            4 => Some(ProverError::DoubleWrite {
+                addr: arg,
+                cycle: None,
+            }),
+            5 => Some(ProverError::CycleLimitExceeded),
+            6 => Some(ProverError::UnknownChannel(arg)),
+            7 => Some(ProverError::InputOverrun),
+            8 => Some(ProverError::HostMemoryConflict { addr: arg }),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ProverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let at = |cycle: &Option<u32>| match cycle {
+            Some(cycle) => format!(" in cycle {cycle}"),
+            None => String::new(),
+        };
+        match self {
+            ProverError::GuestPanic(msg) => write!(f, "Guest faulted: {msg}"),
+            ProverError::MemoryFault { addr, cycle } => {
+                write!(f, "Memory fault at 0x{addr:08X}{}", at(cycle))
+            }
+            ProverError::UnalignedAccess { addr, cycle } => {
+                write!(f, "Unaligned access at 0x{addr:08X}{}", at(cycle))
+            }
+            ProverError::DoubleWrite { addr, cycle } => write!(
+                f,
+                "Double wrote write-once memory at 0x{addr:08X}{}",
+                at(cycle)
+            ),
+            ProverError::CycleLimitExceeded => f.write_str("Cycle limit exceeded"),
+            ProverError::UnknownChannel(channel) => write!(f, "Unknown channel: {channel}"),
+            ProverError::InputOverrun => f.write_str("Read buffer overrun"),
+            ProverError::HostMemoryConflict { addr } => {
+                write!(f, "Host cannot mutate existing memory at 0x{addr:08X}")
+            }
+            ProverError::CallbackFailed { channel, msg } => {
+                write!(f, "Callback for channel {channel} failed: {msg}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProverError {}
+
+#[cfg(test)]
+mod test {
+    use super::ProverError;
+    use crate::host::Exception;
+
+    #[test]
+    fn from_code() {
+        let find = |code, arg, msg: &str| {
+            ProverError::find(&Exception::from_code(msg.into(), code, arg).into())
+        };
+        assert_eq!(
+            find(1, 0, "oops"),
+            Some(ProverError::GuestPanic("oops".into()))
+        );
+        assert_eq!(
+            find(2, 0xabcd, "addr out of range: 0x0000abcd"),
+            Some(ProverError::MemoryFault {
+                addr: 0xabcd,
+                cycle: None
+            })
+        );
+        assert_eq!(
+            find(4, 0x1000, "Double wrote write-once memory at 0x00001000"),
+            Some(ProverError::DoubleWrite {
+                addr: 0x1000,
+                cycle: None
+            })
+        );
+        assert_eq!(
+            find(6, 7, "Unknown channel 7"),
+            Some(ProverError::UnknownChannel(7))
+        );
+        assert_eq!(
+            find(5, 0, "Expand failed: at max steps"),
+            Some(ProverError::CycleLimitExceeded)
+        );
+        assert_eq!(
+            find(7, 0, "Read buffer overrun"),
+            Some(ProverError::InputOverrun)
+        );
+        // Only the code matters, not the message.
+        assert_eq!(find(0, 0, "Read buffer overrun"), None);
+        let exception = Exception::from_code("oops".into(), 1, 0);
+        assert_eq!(exception.what(), "Guest faulted: oops");
+
+        let err = ProverError::UnknownChannel(7);
+        assert_eq!(ProverError::find(&err.clone().into()), Some(err.clone()));
+        let exception = Exception::from(err.clone());
+        assert_eq!(ProverError::find(&exception.into()), Some(err));
+    }
+}
@@ -14,10 +14,13 @@
 
 use std::fmt::{self, Display};
 
+use super::ProverError;
+
 /// A standard exception type used for relaying underlying C++ exceptions.
 #[derive(Debug)]
 pub struct Exception {
     pub(crate) what: String,
+    pub(crate) error: Option<ProverError>,
 }
 
 impl Display for Exception {
@@ -28,11 +31,39 @@ impl Display for Exception {
 
 impl std::error::Error for Exception {}
 
+impl From<super::Cancelled> for Exception {
+    fn from(cancelled: super::Cancelled) -> Self {
+        Exception {
+            what: cancelled.to_string(),
+            error: None,
+        }
+    }
+}
+
+impl From<ProverError> for Exception {
+    fn from(error: ProverError) -> Self {
+        Exception {
+            what: error.to_string(),
+            error: Some(error),
+        }
+    }
+}
+
 impl Exception {
     /// Create a new [Exception] given a string description.
     pub fn new(what: &str) -> Self {
         Exception {
             what: what.to_string(),
+            error: None,
+        }
+    }
+
+    /// Create an [Exception] from a C++ exception, with the error code and
+    /// argument the C++ prover sets for its execution errors.
+    pub(crate) fn from_code(what: String, code: u32, arg: u32) -> Self {
+        match ProverError::from_code(code, arg, &what) {
+            Some(error) => error.into(),
+            None => Exception { what, error: None },
         }
     }
 
@@ -40,4 +71,9 @@ impl Exception {
     pub fn what(&self) -> &str {
         &self.what
     }
+
+    /// Access the [ProverError] this exception reports, if any.
+    pub fn prover_error(&self) -> Option<&ProverError> {
+        self.error.as_ref()
+    }
 }
@@ -12,27 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::{ffi::CStr, mem, os::raw::c_char};
+use std::{cell::RefCell, ffi::CStr, mem, os::raw::c_char};
 
 use super::exception::Exception;
-use super::ProverOpts;
+use super::{Backend, ProverBackend, ProverError, ProverOpts, SendRecvHandler};
+use risc0_zkp::params::ProofParams;
 
 pub(crate) enum RawString {}
 pub(crate) enum RawProver {}
 pub(crate) enum RawReceipt {}
-pub(crate) enum RawMethodId {}
 pub(crate) enum RawU8Buffer {}
 
 #[repr(C)]
 pub(crate) struct RawError {
     msg: *const RawString,
+    code: u32,
+    arg: u32,
 }
 
 impl Default for RawError {
     fn default() -> Self {
         Self {
             msg: std::ptr::null(),
+            code: 0,
+            arg: 0,
         }
     }
 }
@@ -51,7 +54,7 @@ where
             risc0_string_free(err.msg);
             msg
         };
-        Err(Exception { what })
+        Err(Exception::from_code(what, err.code, err.arg))
     }
 }
 
@@ -64,27 +67,6 @@ extern "C" {
 
     pub(crate) fn risc0_u8buffer_new(bytes: *const u8, len: usize) -> *mut RawU8Buffer;
 
-    pub(crate) fn risc0_method_id_compute(
-        err: *mut RawError,
-        elf_contents: *const u8,
-        elf_len: usize,
-        limit: u32,
-    ) -> *const RawMethodId;
-
-    pub(crate) fn risc0_method_id_load(
-        err: *mut RawError,
-        bytes: *const u8,
-        len: usize,
-    ) -> *const RawMethodId;
-
-    pub(crate) fn risc0_method_id_get_buf(
-        err: *mut RawError,
-        ptr: *const RawMethodId,
-        len: *mut u32,
-    ) -> *const u8;
-
-    pub(crate) fn risc0_method_id_free(err: *mut RawError, ptr: *const RawMethodId);
-
     pub(crate) fn risc0_prover_new(
         err: *mut RawError,
         elf_bytes: *const u8,
@@ -169,95 +151,18 @@ extern "C" {
     pub(crate) fn risc0_receipt_free(err: *mut RawError, receipt: *const RawReceipt);
 }
 
-/// A record attesting to the correct execution of a 'method'.
-///
-/// Consists of:
-/// * journal: all data the method wants to publicly output and commit to.
-/// * seal: the cryptographic blob which proves that the receipt is valid.
+/// A receipt held by the C++ prover, used to verify seals it produced.
 pub struct Receipt {
     ptr: *const RawReceipt,
 }
 
-/// The prover generates a [Receipt] by executing a given method in a ZKVM.
+/// The C++ prover, which generates a [Receipt] by executing a given method in
+/// a ZKVM.
 pub struct Prover<'a> {
     ptr: *mut RawProver,
     opts: ProverOpts<'a>,
 }
 
-/// A MethodId represents a unique identifier associated with a particular ELF
-/// binary.
-pub struct MethodId {
-    ptr: *const RawMethodId,
-}
-
-fn into_words(slice: &[u8]) -> super::Result<Vec<u32>> {
-    let mut vec = Vec::new();
-    let chunks = slice.chunks_exact(4);
-    assert!(chunks.remainder().len() == 0);
-    for chunk in chunks {
-        let word = chunk[0] as u32
-            | (chunk[1] as u32) << 8
-            | (chunk[2] as u32) << 16
-            | (chunk[3] as u32) << 24;
-        vec.push(word);
-    }
-    Ok(vec)
-}
-
-impl MethodId {
-    /// Compute the MethodId associated with an existing ELF binary.
-    pub fn compute(elf_contents: &[u8]) -> super::Result<Self> {
-        use crate::method_id::DEFAULT_METHOD_ID_LIMIT;
-        Self::compute_with_limit(elf_contents, DEFAULT_METHOD_ID_LIMIT)
-    }
-
-    /// Compute the MethodId associated with an existing ELF binary.
-    pub fn compute_with_limit(elf_contents: &[u8], limit: u32) -> super::Result<Self> {
-        let mut err = RawError::default();
-        let ptr = unsafe {
-            risc0_method_id_compute(&mut err, elf_contents.as_ptr(), elf_contents.len(), limit)
-        };
-        check(err, || MethodId { ptr })
-    }
-
-    /// Load an existing MethodId from a buffer.
-    pub fn from_slice(slice: &[u8]) -> super::Result<Self> {
-        let mut err = RawError::default();
-        let ptr = unsafe { risc0_method_id_load(&mut err, slice.as_ptr(), slice.len()) };
-        check(err, || MethodId { ptr })
-    }
-
-    /// Access the raw slice of a MethodId.
-    pub fn as_slice(&self) -> super::Result<&[u8]> {
-        let mut err = RawError::default();
-        let mut len: u32 = 0;
-        let ptr = unsafe { risc0_method_id_get_buf(&mut err, self.ptr, &mut len) };
-        check(err, || unsafe {
-            std::slice::from_raw_parts(ptr, len as usize)
-        })
-    }
-}
-
-impl Clone for MethodId {
-    fn clone(&self) -> Self {
-        MethodId::from_slice(self.as_slice().unwrap()).unwrap()
-    }
-}
-
-impl PartialEq for MethodId {
-    fn eq(&self, other: &Self) -> bool {
-        self.as_slice().unwrap() == other.as_slice().unwrap()
-    }
-}
-
-impl Drop for MethodId {
-    fn drop(&mut self) {
-        let mut err = RawError::default();
-        unsafe { risc0_method_id_free(&mut err, self.ptr) };
-        check(err, || ()).unwrap()
-    }
-}
-
 impl Receipt {
     /// Construct a new [Receipt] from individual journal and seal parts.
     pub fn new(journal: &[u8], seal: &[u32]) -> super::Result<Self> {
@@ -307,51 +212,9 @@ impl Receipt {
             Ok(std::slice::from_raw_parts(buf, len))
         }
     }
-
-    /// Provides access to the `journal` of a [Receipt] as a [`Vec<u32>`].
-    pub fn get_journal_vec(&self) -> super::Result<Vec<u32>> {
-        into_words(self.get_journal()?)
-    }
-}
-
-// TODO(nils): Lift "Receipt" from the pure-rust verify implementation so we
-// don't have to proxy through this structure.
-#[derive(Serialize, Deserialize)]
-struct ReceiptData {
-    journal: Vec<u8>,
-    seal: Vec<u32>,
-}
-
-impl Serialize for Receipt {
-    /// Generate a serialized version of the whole receipt.
-    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
-        let data: ReceiptData = ReceiptData {
-            journal: self.get_journal().unwrap().into(),
-            seal: self.get_seal().unwrap().into(),
-        };
-        data.serialize(serializer)
-    }
-}
-
-impl<'de> Deserialize<'de> for Receipt {
-    /// Deserialize a receipt.
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let data = ReceiptData::deserialize(deserializer)?;
-        Ok(Receipt::new(&data.journal, &data.seal).unwrap())
-    }
 }
 
 impl<'a> Prover<'a> {
-    /// Create a new [Prover] with the given method (specified via
-    /// `elf_contents`) and an associated method ID (specified via
-    /// `method_id`).
-    pub fn new(elf_contents: &[u8], method_id: &[u8]) -> super::Result<Self> {
-        Self::new_with_opts(elf_contents, method_id, ProverOpts::default())
-    }
-
     /// Create a new [Prover] with the given method (specified via
     /// `elf_contents`) and an associated method ID (specified via
     /// `method_id`) and additional options.
@@ -388,56 +251,67 @@ impl<'a> Prover<'a> {
         check(err, || ())
     }
 
-    /// Compatibility with pure-rust prover
-    pub fn add_input_u8_slice(&mut self, slice: &[u8]) {
-        let mut v: Vec<u32> = Vec::new();
-        v.resize((slice.len() + 3) / 4, 0);
-        bytemuck::cast_slice_mut(v.as_mut_slice())[..slice.len()].clone_from_slice(slice);
-        self.add_input(v.as_slice()).unwrap()
-    }
-
-    /// Compatibility with pure-rust prover
-    pub fn add_input_u32_slice(&mut self, slice: &[u32]) {
-        self.add_input(slice).unwrap()
-    }
-
-    /// Provide access to private output data written by guest-side method code.
-    pub fn get_output(&self) -> super::Result<&[u8]> {
-        unsafe {
-            let mut err = RawError::default();
-            let buf = risc0_prover_get_output_buf(&mut err, self.ptr);
-            let buf = check(err, || buf)?;
-            let mut err = RawError::default();
-            let len = risc0_prover_get_output_len(&mut err, self.ptr);
-            let len = check(err, || len)?;
-            Ok(std::slice::from_raw_parts(buf, len))
-        }
-    }
-
-    /// Provide access to private output data written to by guest-side method
-    /// code.
-    ///
-    /// This returns the data as a [`Vec<u32>`].
-    pub fn get_output_vec(&self) -> super::Result<Vec<u32>> {
-        into_words(self.get_output()?)
-    }
-
     unsafe extern "C" fn handle_callback(
         channel_id: u32,
         buf: *const u8,
         len: usize,
         cbdata: *const u8,
     ) -> *mut RawU8Buffer {
-        let cb = cbdata as *const Box<dyn Fn(u32, &[u8]) -> Vec<u8>>;
+        let data = &*(cbdata as *const CallbackData);
 
+        // A panic must not unwind into the C++ prover, so it is recorded and
+        // the guest gets an empty reply until the run returns.
         let from_guest = std::slice::from_raw_parts(buf, len);
-        let to_guest = (*cb)(channel_id, from_guest);
+        let to_guest = match ProverError::catch_callback(channel_id, || {
+            // The C++ prover does not report the cycle of the request.
+            data.handler
+                .borrow_mut()
+                .handle(channel_id, None, from_guest)
+        }) {
+            Ok(to_guest) => to_guest,
+            Err(err) => {
+                data.error.borrow_mut().get_or_insert(err);
+                Vec::new()
+            }
+        };
 
         risc0_u8buffer_new(to_guest.as_ptr(), to_guest.len())
     }
 
     /// Execute the ZKVM to produce a [Receipt].
-    pub fn run(&self) -> super::Result<Receipt> {
+    pub fn run(&mut self) -> super::Result<Receipt> {
+        if self.opts.proof_params != ProofParams::DEFAULT {
+            return Err(Exception::new(
+                "Custom proof parameters are only supported by the Rust prover",
+            ));
+        }
+        if self.opts.seed.is_some() {
+            return Err(Exception::new(
+                "Seeded proving is only supported by the Rust prover",
+            ));
+        }
+        if self.opts.trace_sink.is_some() {
+            return Err(Exception::new(
+                "Tracing is only supported by the Rust prover",
+            ));
+        }
+        if self.opts.gdb_server.is_some() {
+            return Err(Exception::new(
+                "Debugging with GDB is only supported by the Rust prover",
+            ));
+        }
+        // The C++ prover can't be interrupted or observed once it starts.
+        if self.opts.progress_callback.is_some() {
+            return Err(Exception::new(
+                "Progress reporting is only supported by the Rust prover",
+            ));
+        }
+        if self.opts.cancellation_token.is_some() {
+            return Err(Exception::new(
+                "Cancellation is only supported by the Rust prover",
+            ));
+        }
+
         let mut err = RawError::default();
 
         unsafe {
@@ -445,16 +319,29 @@ impl<'a> Prover<'a> {
         };
         check(err, || ())?;
 
-        for (channel_id, cb) in self.opts.sendrecv_callbacks.iter() {
+        let callback_error = RefCell::new(None);
+        let callbacks: Vec<_> = self
+            .opts
+            .sendrecv_handlers
+            .iter_mut()
+            .map(|(channel_id, handler)| {
+                let data = CallbackData {
+                    handler: RefCell::new(handler.as_mut()),
+                    error: &callback_error,
+                };
+                (*channel_id, data)
+            })
+            .collect();
+        for (channel_id, data) in callbacks.iter() {
             let mut err = RawError::default();
             unsafe {
-                let cb: *const Box<_> = cb;
+                let data: *const CallbackData = data;
                 risc0_prover_set_sendrecv_handler(
                     &mut err,
                     self.ptr,
                     *channel_id,
                     Self::handle_callback,
-                    cb.cast(),
+                    data.cast(),
                 );
             };
             check(err, || ())?;
@@ -462,7 +349,52 @@ impl<'a> Prover<'a> {
 
         let mut err = RawError::default();
         let ptr = unsafe { risc0_prover_run(&mut err, self.ptr) };
-        check(err, || Receipt { ptr })
+        let receipt = check(err, || Receipt { ptr });
+        match callback_error.into_inner() {
+            Some(err) => Err(err.into()),
+            None => receipt,
+        }
+    }
+}
+
+/// The sendrecv handler of a channel, as passed to
+/// [Prover::handle_callback], along with where to record its failure.
+struct CallbackData<'a, 'b> {
+    handler: RefCell<&'b mut (dyn SendRecvHandler + 'a + Sync)>,
+    error: &'b RefCell<Option<ProverError>>,
+}
+
+impl<'a> ProverBackend for Prover<'a> {
+    fn backend(&self) -> Backend {
+        Backend::Ffi
+    }
+
+    fn add_input_u8_slice(&mut self, slice: &[u8]) -> anyhow::Result<()> {
+        let mut v: Vec<u32> = Vec::new();
+        v.resize((slice.len() + 3) / 4, 0);
+        bytemuck::cast_slice_mut(v.as_mut_slice())[..slice.len()].clone_from_slice(slice);
+        Ok(self.add_input(v.as_slice())?)
+    }
+
+    fn add_input_u32_slice(&mut self, slice: &[u32]) -> anyhow::Result<()> {
+        Ok(self.add_input(slice)?)
+    }
+
+    fn get_output(&self) -> anyhow::Result<&[u8]> {
+        unsafe {
+            let mut err = RawError::default();
+            let buf = risc0_prover_get_output_buf(&mut err, self.ptr);
+            let buf = check(err, || buf)?;
+            let mut err = RawError::default();
+            let len = risc0_prover_get_output_len(&mut err, self.ptr);
+            let len = check(err, || len)?;
+            Ok(std::slice::from_raw_parts(buf, len))
+        }
+    }
+
+    fn run(&mut self) -> anyhow::Result<super::Receipt> {
+        let receipt = Prover::run(self)?;
+        super::Receipt::new_with_backend(receipt.get_journal()?, receipt.get_seal()?, Backend::Ffi)
     }
 }
 
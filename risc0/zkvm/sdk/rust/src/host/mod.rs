@@ -17,18 +17,38 @@
 
 use std::collections::HashMap;
 
-#[cfg(not(feature = "pure-prove"))]
-mod ffi;
-#[cfg(feature = "pure-prove")]
-mod prove;
-#[cfg(not(feature = "pure-prove"))]
-use ffi as prove;
+use anyhow::{anyhow, bail};
+use risc0_zkp::params::ProofParams;
+use serde::Deserialize;
 
-pub use prove::Prover;
+use crate::{
+    envelope::{circuit_digest, le_bytes_to_words, HashFunction, ReceiptEnvelope, ReceiptHeader},
+    serde::{from_slice, to_vec, Deserializer},
+};
 
+mod error;
 mod exception;
+mod ffi;
+mod progress;
+mod prove;
+mod receipt;
+mod sendrecv;
 
+pub use crate::envelope::Backend;
+pub use crate::prove::{
+    gdb::GdbServer,
+    profiler::Profiler,
+    trace::{TraceEvent, TraceSink},
+    ExitStatus, SessionSummary, Trace,
+};
+pub use error::ProverError;
 pub use exception::Exception;
+pub(crate) use progress::ProgressCallback;
+pub use progress::{CancellationToken, ProverProgress};
+pub use prove::{Prover, ProverBackend};
+pub use receipt::Receipt;
+pub use risc0_zkp::prove::{Cancelled, ProvePhase};
+pub use sendrecv::SendRecvHandler;
 
 #[cxx::bridge]
 mod bridge {}
@@ -37,37 +57,143 @@ mod bridge {}
 pub struct ProverOpts<'a> {
     pub(crate) skip_seal: bool,
 
-    pub(crate) sendrecv_callbacks: HashMap<u32, Box<dyn Fn(u32, &[u8]) -> Vec<u8> + 'a + Sync>>,
+    pub(crate) proof_params: ProofParams,
+
+    pub(crate) sendrecv_handlers: HashMap<u32, Box<dyn SendRecvHandler + 'a + Sync>>,
+
+    pub(crate) progress_callback: Option<ProgressCallback<'a>>,
+
+    pub(crate) cancellation_token: Option<CancellationToken>,
+
+    pub(crate) seed: Option<u64>,
+
+    pub(crate) backend: Backend,
+
+    pub(crate) trace_sink: Option<Box<dyn TraceSink + 'a + Send>>,
+
+    pub(crate) gdb_server: Option<GdbServer>,
 }
 
 impl<'a> ProverOpts<'a> {
     /// If true, skip generating the seal in receipt.  This should
     /// only be used for testing.  In this case, performace will be
     /// much better but we will not be able to cryptographically
-    /// verify the execution.
+    /// verify the execution. The Rust prover then runs the guest on its
+    /// emulator, unless a trace sink or a GDB server is set.
     pub fn with_skip_seal(self, skip_seal: bool) -> Self {
         Self { skip_seal, ..self }
     }
 
+    /// Sets the [ProofParams] used to generate the seal, trading proving
+    /// time and seal size against security. A non-default `inv_rate` also
+    /// changes the code roots, so the [MethodId] must be computed with the
+    /// same parameters. Parameters that are not valid, or that use more than
+    /// [ZK_CYCLES](risc0_zkp::ZK_CYCLES) queries, make the run fail. Only
+    /// supported by the Rust prover.
+    pub fn with_proof_params(self, proof_params: ProofParams) -> Self {
+        Self {
+            proof_params,
+            ..self
+        }
+    }
+
     /// Add a callback handler for sendrecv ports, indexed by channel
     /// numbers.  The guest can call these callbacks by invoking
     /// risc0_zkvm_guest::host_sendrecv.
     pub fn with_sendrecv_callback(
-        mut self,
+        self,
         channel_id: u32,
         callback: impl Fn(u32, &[u8]) -> Vec<u8> + 'a + Sync,
     ) -> Self {
-        self.sendrecv_callbacks
-            .insert(channel_id, Box::new(callback));
+        self.with_sendrecv_handler(channel_id, sendrecv::Callback(callback))
+    }
+
+    /// Registers a [SendRecvHandler] to serve the sendrecv requests the guest
+    /// makes on the given channel, replacing any handler or callback already
+    /// registered for it.
+    pub fn with_sendrecv_handler(
+        mut self,
+        channel_id: u32,
+        handler: impl SendRecvHandler + 'a + Sync,
+    ) -> Self {
+        self.sendrecv_handlers.insert(channel_id, Box::new(handler));
         self
     }
+
+    /// Sets a callback that is called as each [ProvePhase] of the run
+    /// starts, and periodically while the guest executes. Only supported by
+    /// the Rust prover, since the FFI-based prover can't report its progress.
+    pub fn with_progress_callback(self, callback: impl Fn(&ProverProgress) + 'a + Sync) -> Self {
+        Self {
+            progress_callback: Some(Box::new(callback)),
+            ..self
+        }
+    }
+
+    /// Sets a [CancellationToken] that stops the run with a [Cancelled] error
+    /// once it is cancelled. Only supported by the Rust prover, since the
+    /// FFI-based prover can't be interrupted once it starts.
+    pub fn with_cancellation_token(self, cancellation_token: CancellationToken) -> Self {
+        Self {
+            cancellation_token: Some(cancellation_token),
+            ..self
+        }
+    }
+
+    /// Seeds every random choice the prover makes, so that the same guest and
+    /// inputs always produce the same seal. The seal is then NOT
+    /// zero-knowledge: the padding meant to hide the execution trace can be
+    /// recomputed by anyone who knows the seed. This should only be used for
+    /// testing. Only supported by the Rust prover.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Chooses the [Backend] that runs and proves the guest. Defaults to the
+    /// Rust prover with the `pure-prove` feature, and to the FFI-based prover
+    /// otherwise.
+    pub fn with_backend(self, backend: Backend) -> Self {
+        Self { backend, ..self }
+    }
+
+    /// Reports every instruction the guest runs, and every memory access, to
+    /// a [TraceSink] such as a [Profiler]. This slows execution down, and
+    /// nothing is reported when proving a saved [Trace]. Only supported by the
+    /// Rust prover.
+    pub fn with_trace_sink(self, sink: impl TraceSink + 'a + Send) -> Self {
+        Self {
+            trace_sink: Some(Box::new(sink)),
+            ..self
+        }
+    }
+
+    /// Runs the guest under GDB, which connects through a [GdbServer] and
+    /// can stop it at breakpoints and inspect it. The guest is always run
+    /// without a seal, as with [ProverOpts::with_skip_seal]. Only supported by
+    /// the Rust prover.
+    pub fn with_gdb_server(self, server: GdbServer) -> Self {
+        Self {
+            gdb_server: Some(server),
+            ..self
+        }
+    }
 }
 
 impl<'a> Default for ProverOpts<'a> {
     fn default() -> ProverOpts<'a> {
         ProverOpts {
             skip_seal: false,
-            sendrecv_callbacks: HashMap::new(),
+            proof_params: ProofParams::default(),
+            sendrecv_handlers: HashMap::new(),
+            progress_callback: None,
+            cancellation_token: None,
+            seed: None,
+            backend: Backend::default(),
+            trace_sink: None,
+            gdb_server: None,
         }
     }
 }
@@ -78,48 +204,209 @@ pub const DEFAULT_METHOD_ID_LIMIT: u32 = 12;
 /// A Result specialized for [Exception].
 pub type Result<T> = std::result::Result<T, Exception>;
 
-pub use prove::{MethodId, Receipt};
+pub use crate::method_id::{MethodDigest, MethodId};
+
+// The number of seal words holding the circuit outputs, which are followed by
+// the po2 of the execution trace.
+fn seal_output_words(backend: Backend) -> usize {
+    match backend {
+        Backend::Ffi => 9,
+        Backend::Rust => 18,
+    }
+}
+
+impl Receipt {
+    /// Wraps this [Receipt] in a versioned [ReceiptEnvelope], optionally
+    /// including the method ID it is expected to verify against.
+    pub fn to_envelope(&self, method_id: Option<&[u8]>) -> anyhow::Result<ReceiptEnvelope> {
+        let seal = self.get_seal()?;
+        Ok(ReceiptEnvelope {
+            header: ReceiptHeader {
+                hash_fn: HashFunction::Sha256,
+                po2: seal
+                    .get(seal_output_words(self.backend()))
+                    .copied()
+                    .unwrap_or(0),
+                circuit: circuit_digest(),
+            },
+            journal: self.get_journal()?.to_vec(),
+            seal: seal.to_vec(),
+            method_id: method_id.map(|id| id.to_vec()),
+            backend: Some(self.backend()),
+        })
+    }
+
+    /// Constructs a [Receipt] from a [ReceiptEnvelope], checking that the
+    /// envelope was produced for this circuit.
+    pub fn from_envelope(envelope: &ReceiptEnvelope) -> anyhow::Result<Self> {
+        if envelope.header.circuit != circuit_digest() {
+            bail!("Receipt envelope was produced for a different circuit");
+        }
+        let backend = envelope.backend.unwrap_or_default();
+        if let Some(po2) = envelope.seal.get(seal_output_words(backend)) {
+            if *po2 != envelope.header.po2 {
+                bail!(
+                    "Receipt envelope po2 ({}) does not match seal ({})",
+                    envelope.header.po2,
+                    po2
+                );
+            }
+        }
+        Receipt::new_with_backend(&envelope.journal, &envelope.seal, backend)
+    }
+
+    /// Decodes a [ReceiptEnvelope] and verifies it against a [MethodDigest],
+    /// taking the code roots from the method ID recorded in the envelope.
+    pub fn verify_envelope(
+        envelope: &ReceiptEnvelope,
+        digest: &MethodDigest,
+    ) -> anyhow::Result<Self> {
+        let table = match &envelope.method_id {
+            Some(table) => table,
+            None => bail!("Receipt envelope does not record its method ID"),
+        };
+        let receipt = Receipt::from_envelope(envelope)?;
+        receipt.verify_with_digest(digest, table)?;
+        Ok(receipt)
+    }
+
+    /// Returns a [Deserializer] over the journal, which decodes the values
+    /// committed by the guest one at a time, in order, without copying the
+    /// journal.
+    pub fn journal_reader(&self) -> anyhow::Result<Deserializer<'_>> {
+        let journal = bytemuck::try_cast_slice(self.get_journal()?)
+            .map_err(|err| anyhow!("Journal is not word aligned: {:?}", err))?;
+        Ok(Deserializer::new(journal))
+    }
+
+    /// Decodes the journal as a single committed value, failing if any of the
+    /// journal is left unread.
+    pub fn decode_journal<'a, T: Deserialize<'a>>(&'a self) -> anyhow::Result<T> {
+        let mut reader = self.journal_reader()?;
+        let value = reader.read()?;
+        reader.finish()?;
+        Ok(value)
+    }
+
+    /// Decodes a [Receipt] from either a [ReceiptEnvelope] or the bare
+    /// [to_vec](crate::serde::to_vec) encoding of a [Receipt]. Bare encodings
+    /// written before the backend was recorded end right after the seal, and
+    /// decode as receipts of the [Backend::Ffi] prover that wrote them.
+    pub fn from_words(words: &[u32]) -> anyhow::Result<Self> {
+        if ReceiptEnvelope::is_envelope(words) {
+            return Receipt::from_envelope(&ReceiptEnvelope::from_words(words)?);
+        }
+        let legacy: crate::receipt::Receipt = from_slice(words)?;
+        if to_vec(&legacy)?.len() == words.len() {
+            return Ok(Receipt::from_inner(legacy, Backend::Ffi));
+        }
+        Ok(from_slice(words)?)
+    }
+
+    /// Decodes a [Receipt] from little-endian bytes, as written to a file by
+    /// `r0vm`. Both envelopes and the bare encoding are accepted.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Receipt::from_words(&le_bytes_to_words(bytes)?)
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use super::{MethodId, Prover, ProverOpts, Receipt};
+    use super::{
+        Backend, GdbServer, MethodId, Profiler, Prover, ProverError, ProverOpts, Receipt,
+        SendRecvHandler, TraceEvent, TraceSink,
+    };
     use crate::{
         platform::memory::{COMMIT, HEAP},
-        serde::{from_slice, to_vec},
+        receipt::ReceiptClaim,
+        serde::to_vec,
     };
     use anyhow::Result;
-    use risc0_zkp::core::sha::Digest;
+    use risc0_zkp::core::sha::{default_implementation, Digest, DIGEST_WORDS};
     use risc0_zkvm_methods::{
         FAIL_ID, FAIL_PATH, IO_ID, IO_PATH, SENDRECV_ID, SENDRECV_PATH, SHA_ID, SHA_PATH,
+        VERIFY_ID, VERIFY_PATH,
     };
     use std::sync::Mutex;
     use test_log::test;
 
-    #[test]
-    fn sha() {
+    // Runs each of the given tests, which take the [Backend] to run on,
+    // against every backend.
+    macro_rules! conformance {
+        ($($name:ident),* $(,)?) => {
+            mod ffi {
+                use test_log::test;
+                $(
+                    #[test]
+                    fn $name() {
+                        super::$name(super::Backend::Ffi)
+                    }
+                )*
+            }
+
+            mod rust {
+                use test_log::test;
+                $(
+                    #[test]
+                    fn $name() {
+                        super::$name(super::Backend::Rust)
+                    }
+                )*
+            }
+        };
+    }
+
+    conformance!(
+        sha,
+        journal_reader,
+        input_padding,
+        memory_io,
+        receipt_serde,
+        receipt_serde_no_seal,
+        receipt_wrong_method_id,
+        receipt_tampered_journal,
+        receipt_tampered_long_journal,
+        receipt_corrupted_seal,
+        receipt_envelope,
+        verify_in_guest,
+        fail,
+        host_sendrecv,
+        host_sendrecv_callback_panic,
+        host_sendrecv_handler,
+        host_sendrecv_unknown_channel,
+    );
+
+    fn backend_opts(backend: Backend) -> ProverOpts<'static> {
+        ProverOpts::default().with_backend(backend)
+    }
+
+    fn sha(backend: Backend) {
         assert_eq!(
-            run_sha(""),
+            run_sha(backend, ""),
             Digest::new([
                 0xe3b0c442, 0x98fc1c14, 0x9afbf4c8, 0x996fb924, 0x27ae41e4, 0x649b934c, 0xa495991b,
                 0x7852b855,
             ])
         );
         assert_eq!(
-            run_sha("a"),
+            run_sha(backend, "a"),
             Digest::new([
                 0xca978112, 0xca1bbdca, 0xfac231b3, 0x9a23dc4d, 0xa786eff8, 0x147c4e72, 0xb9807785,
                 0xafee48bb,
             ])
         );
         assert_eq!(
-            run_sha("abc"),
+            run_sha(backend, "abc"),
             Digest::new([
                 0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
                 0xf20015ad
             ])
         );
         assert_eq!(
-            run_sha("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            run_sha(
+                backend,
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ),
             Digest::new([
                 0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
                 0x19db06c1
@@ -127,41 +414,80 @@ mod test {
         );
     }
 
-    fn run_sha(msg: &str) -> Digest {
-        let mut prover = Prover::new(&std::fs::read(SHA_PATH).unwrap(), SHA_ID).unwrap();
+    fn run_sha(backend: Backend, msg: &str) -> Digest {
+        let mut prover = Prover::new_with_opts(
+            &std::fs::read(SHA_PATH).unwrap(),
+            SHA_ID,
+            backend_opts(backend),
+        )
+        .unwrap();
         let vec = to_vec(&msg).unwrap();
-        prover.add_input_u32_slice(vec.as_slice());
+        prover.add_input_u32_slice(vec.as_slice()).unwrap();
         let receipt = prover.run().unwrap();
-        let vec = receipt.get_journal_vec().unwrap();
-        from_slice::<Digest>(vec.as_slice()).unwrap()
+        receipt.decode_journal().unwrap()
     }
 
-    #[test]
-    fn memory_io() {
+    fn journal_reader(backend: Backend) {
+        let mut prover = Prover::new_with_opts(
+            &std::fs::read(SHA_PATH).unwrap(),
+            SHA_ID,
+            backend_opts(backend),
+        )
+        .unwrap();
+        prover
+            .add_input_u32_slice(&to_vec(&"abc").unwrap())
+            .unwrap();
+        let receipt = prover.run().unwrap();
+
+        let mut reader = receipt.journal_reader().unwrap();
+        let first: u32 = reader.read().unwrap();
+        assert_eq!(first, 0xba7816bf);
+        assert!(reader.finish().is_err());
+        let rest: [u32; 7] = reader.read().unwrap();
+        assert_eq!(rest[6], 0xf20015ad);
+        reader.finish().unwrap();
+
+        assert!(receipt.decode_journal::<u32>().is_err());
+    }
+
+    fn input_padding(backend: Backend) {
+        // A partial word of input is padded with zeros, so the words after it
+        // stay aligned.
+        let opts = backend_opts(backend).with_skip_seal(true);
+        let mut prover =
+            Prover::new_with_opts(&std::fs::read(IO_PATH).unwrap(), IO_ID, opts).unwrap();
+        prover.add_input_u8_slice(&[1]).unwrap();
+        prover
+            .add_input_u32_slice(&[HEAP.start() as u32, 0])
+            .unwrap();
+        prover.run().unwrap();
+    }
+
+    fn memory_io(backend: Backend) {
         // Double write to WOM are fine
-        assert!(run_memio(&[(COMMIT.start(), 1), (COMMIT.start(), 1)]).is_ok());
+        assert!(run_memio(backend, &[(COMMIT.start(), 1), (COMMIT.start(), 1)]).is_ok());
 
         // Double write to WOM with different values throw
-        assert!(run_memio(&[(COMMIT.start(), 1), (COMMIT.start(), 2)]).is_err());
+        assert!(run_memio(backend, &[(COMMIT.start(), 1), (COMMIT.start(), 2)]).is_err());
 
         // But they are OK at different addresses
-        assert!(run_memio(&[(COMMIT.start(), 1), (COMMIT.start() + 4, 2)]).is_ok());
+        assert!(run_memio(backend, &[(COMMIT.start(), 1), (COMMIT.start() + 4, 2)]).is_ok());
 
         // Aligned write is fine
-        assert!(run_memio(&[(HEAP.start(), 1)]).is_ok());
+        assert!(run_memio(backend, &[(HEAP.start(), 1)]).is_ok());
 
         // Unaligned write is bad
-        assert!(run_memio(&[(HEAP.start() + 1, 1)]).is_err());
+        assert!(run_memio(backend, &[(HEAP.start() + 1, 1)]).is_err());
 
         // Aligned read is fine
-        assert!(run_memio(&[(HEAP.start(), 0)]).is_ok());
+        assert!(run_memio(backend, &[(HEAP.start(), 0)]).is_ok());
 
         // Unaligned read is bad
-        assert!(run_memio(&[(HEAP.start() + 1, 0)]).is_err());
+        assert!(run_memio(backend, &[(HEAP.start() + 1, 0)]).is_err());
     }
 
-    fn run_memio(pairs: &[(usize, usize)]) -> Result<Receipt> {
-        run_memio_with_opts(pairs, ProverOpts::default().with_skip_seal(true))
+    fn run_memio(backend: Backend, pairs: &[(usize, usize)]) -> Result<Receipt> {
+        run_memio_with_opts(pairs, backend_opts(backend).with_skip_seal(true))
     }
 
     fn run_memio_with_opts(pairs: &[(usize, usize)], opts: ProverOpts) -> Result<Receipt> {
@@ -174,7 +500,7 @@ mod test {
         let skip_seal = opts.skip_seal;
         let mut prover =
             Prover::new_with_opts(&std::fs::read(IO_PATH).unwrap(), IO_ID, opts).unwrap();
-        prover.add_input_u32_slice(vec.as_slice());
+        prover.add_input_u32_slice(vec.as_slice()).unwrap();
         let receipt = prover.run()?;
         if !skip_seal {
             receipt.verify(IO_ID).unwrap();
@@ -182,22 +508,25 @@ mod test {
         Ok(receipt)
     }
 
-    #[test]
-    fn receipt_serde() {
+    fn receipt_serde(backend: Backend) {
         let receipt: Receipt =
-            run_memio_with_opts(&[(HEAP.start(), 0)], ProverOpts::default()).unwrap();
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(backend)).unwrap();
         let ser: Vec<u32> = crate::serde::to_vec(&receipt).unwrap();
         let de: Receipt = crate::serde::from_slice(&ser).unwrap();
         assert_eq!(de.get_journal().unwrap(), receipt.get_journal().unwrap());
         assert_eq!(de.get_seal().unwrap(), receipt.get_seal().unwrap());
+        assert_eq!(de.backend(), backend);
         de.verify(IO_ID).unwrap();
+
+        let mut unknown = ser.clone();
+        *unknown.last_mut().unwrap() = 3;
+        assert!(crate::serde::from_slice::<Receipt>(&unknown).is_err());
     }
 
-    #[test]
-    fn receipt_serde_no_seal() {
+    fn receipt_serde_no_seal(backend: Backend) {
         let receipt: Receipt = run_memio_with_opts(
             &[(HEAP.start(), 0)],
-            ProverOpts::default().with_skip_seal(true),
+            backend_opts(backend).with_skip_seal(true),
         )
         .unwrap();
         let ser: Vec<u32> = crate::serde::to_vec(&receipt).unwrap();
@@ -207,11 +536,577 @@ mod test {
         assert!(de.verify(IO_ID).is_err());
     }
 
+    fn receipt_wrong_method_id(backend: Backend) {
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(backend)).unwrap();
+        receipt.verify(IO_ID).unwrap();
+        assert!(receipt.verify(SHA_ID).is_err());
+    }
+
+    fn receipt_tampered_journal(backend: Backend) {
+        let mut prover = Prover::new_with_opts(
+            &std::fs::read(SHA_PATH).unwrap(),
+            SHA_ID,
+            backend_opts(backend),
+        )
+        .unwrap();
+        prover
+            .add_input_u32_slice(to_vec(&"abc").unwrap().as_slice())
+            .unwrap();
+        let receipt = prover.run().unwrap();
+        receipt.verify(SHA_ID).unwrap();
+
+        let seal = receipt.get_seal().unwrap();
+        let mut journal = receipt.get_journal_vec().unwrap();
+        journal[0] ^= 1;
+        let tampered =
+            Receipt::new_with_backend(bytemuck::cast_slice(journal.as_slice()), seal, backend)
+                .unwrap();
+        assert!(tampered.verify(SHA_ID).is_err());
+    }
+
+    fn receipt_tampered_long_journal(backend: Backend) {
+        // The claim the verify guest commits is too long to be placed in the
+        // result registers as is, so the seal only holds its digest.
+        let inner: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(Backend::Rust)).unwrap();
+        let receipt = run_verify(backend, IO_ID, &inner).unwrap();
+        receipt.verify(VERIFY_ID).unwrap();
+
+        let seal = receipt.get_seal().unwrap();
+        let mut journal = receipt.get_journal_vec().unwrap();
+        assert!(journal.len() > DIGEST_WORDS);
+        *journal.last_mut().unwrap() ^= 1;
+        let tampered =
+            Receipt::new_with_backend(bytemuck::cast_slice(journal.as_slice()), seal, backend)
+                .unwrap();
+        assert!(tampered.verify(VERIFY_ID).is_err());
+    }
+
+    fn receipt_corrupted_seal(backend: Backend) {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(backend)).unwrap();
+        let journal = receipt.get_journal().unwrap();
+        let seal = receipt.get_seal().unwrap();
+        let new = |seal: &[u32]| Receipt::new_with_backend(journal, seal, backend).unwrap();
+
+        let mut extended = seal.to_vec();
+        extended.push(0);
+        assert!(new(&extended).verify(IO_ID).is_err());
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..16 {
+            // A truncated seal is always rejected.
+            let len = rng.gen_range(0..seal.len());
+            assert!(new(&seal[..len]).verify(IO_ID).is_err());
+
+            // A corrupted word may or may not be rejected, since it can
+            // encode the same field element, but it must never panic.
+            let mut corrupted = seal.to_vec();
+            let idx = rng.gen_range(0..corrupted.len());
+            corrupted[idx] = rng.gen();
+            let _ = new(&corrupted).verify(IO_ID);
+        }
+    }
+
+    fn receipt_envelope(backend: Backend) {
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(backend)).unwrap();
+
+        let envelope = receipt.to_envelope(Some(IO_ID)).unwrap();
+        assert_eq!(envelope.method_id.as_deref(), Some(IO_ID));
+        assert_eq!(envelope.backend, Some(backend));
+        let decoded = Receipt::from_bytes(&envelope.to_bytes()).unwrap();
+        assert_eq!(
+            decoded.get_journal().unwrap(),
+            receipt.get_journal().unwrap()
+        );
+        assert_eq!(decoded.backend(), backend);
+        decoded.verify(IO_ID).unwrap();
+
+        // Envelopes from before the backend was recorded use the default.
+        let mut older = envelope.clone();
+        older.backend = None;
+        let decoded = Receipt::from_envelope(&older).unwrap();
+        assert_eq!(decoded.backend(), Backend::default());
+
+        // The bare serde encoding is still accepted.
+        let bare = Receipt::from_words(&to_vec(&receipt).unwrap()).unwrap();
+        assert_eq!(bare.get_journal().unwrap(), receipt.get_journal().unwrap());
+        assert_eq!(bare.get_seal().unwrap(), receipt.get_seal().unwrap());
+
+        let mut newer = envelope.to_words();
+        newer[1] = 3 << 16;
+        assert!(Receipt::from_words(&newer).is_err());
+    }
+
+    #[test]
+    fn receipt_legacy_encoding() {
+        // A journal of two words and a seal of three, without a backend.
+        let legacy = [2, 0x11, 0x22, 3, 1, 2, 3];
+        let receipt = Receipt::from_words(&legacy).unwrap();
+        assert_eq!(receipt.backend(), Backend::Ffi);
+        assert_eq!(receipt.get_journal_vec().unwrap(), [0x11, 0x22]);
+        assert_eq!(receipt.get_seal().unwrap(), [1, 2, 3]);
+
+        let current = [&legacy[..], &[Backend::Rust as u32]].concat();
+        let receipt = Receipt::from_words(&current).unwrap();
+        assert_eq!(receipt.backend(), Backend::Rust);
+        assert_eq!(to_vec(&receipt).unwrap(), current);
+        assert!(Receipt::from_words(&legacy[..legacy.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn method_digest() {
+        use super::MethodDigest;
+
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(Backend::Rust)).unwrap();
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let method_id = MethodId::compute(&elf).unwrap();
+        let table = method_id.as_slice().unwrap();
+        let digest = method_id.digest().unwrap();
+
+        receipt.verify_with_digest(&digest, table).unwrap();
+        let envelope = receipt.to_envelope(Some(table)).unwrap();
+        Receipt::verify_envelope(&envelope, &digest).unwrap();
+
+        // The table must be complete, and match the digest.
+        assert!(receipt.verify_with_digest(&digest, IO_ID).is_err());
+        let other = MethodDigest::from_bytes(&[0; 32]).unwrap();
+        assert!(receipt.verify_with_digest(&other, table).is_err());
+        let bare = receipt.to_envelope(None).unwrap();
+        assert!(Receipt::verify_envelope(&bare, &digest).is_err());
+    }
+
     #[test]
-    fn fail() {
+    fn receipt_inspect() {
+        use risc0_zkp::{core::sha::DIGEST_WORDS, MIN_CYCLES_PO2};
+
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(Backend::Rust)).unwrap();
+        let info = receipt.inspect().unwrap();
+        assert_eq!(info.cycles, 1 << info.po2);
+        assert_eq!(info.sizes.total(), receipt.get_seal().unwrap().len());
+        assert_eq!(
+            info.outputs[DIGEST_WORDS] as usize,
+            receipt.get_journal().unwrap().len()
+        );
+        let method_id = MethodId::from_slice(IO_ID).unwrap();
+        assert_eq!(
+            info.code_root,
+            method_id.table[info.po2 as usize - MIN_CYCLES_PO2]
+        );
+
+        // Truncating or padding the query section must not go unnoticed.
+        let journal = receipt.get_journal().unwrap();
+        let seal = receipt.get_seal().unwrap();
+        let short = Receipt::new(journal, &seal[..seal.len() - 1]).unwrap();
+        assert!(short.inspect().is_err());
+        let long = Receipt::new(journal, &[seal, &[0]].concat()).unwrap();
+        assert!(long.inspect().is_err());
+
+        // Neither may a seal that asks for a huge number of queries.
+        let mut huge = seal.to_vec();
+        huge[info.sizes.header] = u32::MAX;
+        let huge = Receipt::new(journal, &huge).unwrap();
+        assert!(huge.inspect().is_err());
+        assert!(huge.verify_with_min_security(IO_ID, 0).is_err());
+    }
+
+    #[test]
+    fn proof_params() {
+        use risc0_zkp::{params::ProofParams, ZK_CYCLES};
+
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+
+        // Fewer queries give a cheaper but less secure proof.
+        let cheap = ProofParams::default().with_queries(25);
+        let opts = backend_opts(Backend::Rust).with_proof_params(cheap);
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let receipt = prover.run().unwrap();
+        assert_eq!(receipt.inspect().unwrap().params, cheap);
+        assert!(receipt.verify(IO_ID).is_err());
+        receipt
+            .verify_with_min_security(IO_ID, cheap.security_bits())
+            .unwrap();
+
+        // A larger inv_rate changes the code roots.
+        let secure = ProofParams::default().with_inv_rate(8);
+        let method_id = MethodId::compute_with_params(&elf, 12, &secure).unwrap();
+        let method_id = method_id.as_slice().unwrap();
+        let opts = backend_opts(Backend::Rust).with_proof_params(secure);
+        let mut prover = Prover::new_with_opts(&elf, method_id, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let receipt = prover.run().unwrap();
+        assert_eq!(receipt.inspect().unwrap().params, secure);
+        receipt.verify(method_id).unwrap();
+        assert!(receipt.verify(IO_ID).is_err());
+
+        // Parameters the prover can't use are an error rather than a panic.
+        for params in [
+            ProofParams::default().with_queries(0),
+            ProofParams::default().with_queries(ZK_CYCLES + 1),
+            ProofParams::default().with_inv_rate(3),
+        ] {
+            let opts = backend_opts(Backend::Rust).with_proof_params(params);
+            let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+            prover.add_input_u32_slice(&input).unwrap();
+            assert!(prover.run().is_err());
+        }
+    }
+
+    #[test]
+    fn seed() {
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+        let run = |opts| {
+            let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+            prover.add_input_u32_slice(&input).unwrap();
+            prover.run().unwrap()
+        };
+
+        let a = run(backend_opts(Backend::Rust).with_seed(1));
+        let b = run(backend_opts(Backend::Rust).with_seed(1));
+        let c = run(backend_opts(Backend::Rust).with_seed(2));
+        a.verify(IO_ID).unwrap();
+        c.verify(IO_ID).unwrap();
+        assert_eq!(a.get_seal().unwrap(), b.get_seal().unwrap());
+        assert_ne!(a.get_seal().unwrap(), c.get_seal().unwrap());
+
+        // Unseeded proofs stay randomized.
+        let d = run(backend_opts(Backend::Rust));
+        let e = run(backend_opts(Backend::Rust));
+        assert_ne!(d.get_seal().unwrap(), e.get_seal().unwrap());
+    }
+
+    #[test]
+    fn batch_verify() {
+        use crate::{receipt, verify::BatchVerifier};
+
+        let receipt: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(Backend::Rust)).unwrap();
+        let journal = receipt.get_journal().unwrap();
+        let seal = receipt.get_seal().unwrap();
+        let receipts = [
+            receipt::Receipt::new(journal, seal).unwrap(),
+            receipt::Receipt::new(journal, &seal[..seal.len() - 1]).unwrap(),
+            receipt::Receipt::new(journal, seal).unwrap(),
+        ];
+
+        let results = BatchVerifier::new(IO_ID).unwrap().verify_all(&receipts);
+        assert_eq!(results.len(), receipts.len());
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+
+        let results = BatchVerifier::new(SHA_ID).unwrap().verify_all(&receipts);
+        assert!(results.iter().all(|result| result.is_err()));
+    }
+
+    #[test]
+    fn progress() {
+        use super::{CancellationToken, Cancelled, ProvePhase};
+
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+
+        let phases = Mutex::new(Vec::new());
+        let opts = backend_opts(Backend::Rust).with_progress_callback(|progress| {
+            phases.lock().unwrap().push(progress.phase);
+        });
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        prover.run().unwrap();
+        let mut phases = phases.lock().unwrap().clone();
+        phases.dedup();
+        assert_eq!(
+            phases,
+            [
+                ProvePhase::Execute,
+                ProvePhase::Commit,
+                ProvePhase::Accumulate,
+                ProvePhase::Check,
+                ProvePhase::Fri,
+            ]
+        );
+
+        // Cancel the run once it starts on the check polynomial.
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let opts = backend_opts(Backend::Rust)
+            .with_cancellation_token(token.clone())
+            .with_progress_callback(move |progress| {
+                if progress.phase == ProvePhase::Check {
+                    canceller.cancel();
+                }
+            });
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let err = prover.run().err().unwrap();
+        assert!(err.is::<Cancelled>());
+
+        // A token cancelled in advance stops the run during execution.
+        let opts = backend_opts(Backend::Rust).with_cancellation_token(token);
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        assert!(prover.run().err().unwrap().is::<Cancelled>());
+    }
+
+    #[test]
+    fn execute() {
+        use super::ExitStatus;
+
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, backend_opts(Backend::Rust)).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let summary = prover.execute().unwrap();
+        assert_eq!(summary.exit_status, ExitStatus::Halted);
+        assert!(summary.cycles < 1 << summary.po2);
+        assert!(summary.memory_bytes > 0);
+
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, backend_opts(Backend::Rust)).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let receipt = prover.run().unwrap();
+        assert_eq!(summary.journal, receipt.get_journal_vec().unwrap());
+        assert_eq!(summary.po2, receipt.inspect().unwrap().po2);
+
+        // Without a seal, running takes the emulator too.
+        let opts = backend_opts(Backend::Rust).with_skip_seal(true);
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let unsealed = prover.run().unwrap();
+        assert_eq!(
+            unsealed.get_journal().unwrap(),
+            receipt.get_journal().unwrap()
+        );
+        assert!(unsealed.get_seal().unwrap().is_empty());
+
+        let elf = std::fs::read(FAIL_PATH).unwrap();
+        let mut prover = Prover::new_with_opts(&elf, FAIL_ID, backend_opts(Backend::Rust)).unwrap();
+        let summary = prover.execute().unwrap();
+        assert!(matches!(summary.exit_status, ExitStatus::Fault(_)));
+    }
+
+    #[test]
+    fn emulator_matches_circuit() {
+        use super::SessionSummary;
+
+        // A trace sink makes the prover execute the guest in the circuit.
+        struct Discard;
+
+        impl TraceSink for Discard {
+            fn on_event(&mut self, _event: &TraceEvent) {}
+        }
+
+        fn execute(elf: &[u8], id: &[u8], input: &[u32], circuit: bool) -> SessionSummary {
+            let mut opts = backend_opts(Backend::Rust);
+            if circuit {
+                opts = opts.with_trace_sink(Discard);
+            }
+            let mut prover = Prover::new_with_opts(elf, id, opts).unwrap();
+            prover.add_input_u32_slice(input).unwrap();
+            prover.execute().unwrap()
+        }
+
+        let guests = [
+            (
+                IO_PATH,
+                IO_ID,
+                vec![2, HEAP.start() as u32, 1, HEAP.start() as u32 + 4, 2],
+            ),
+            (SHA_PATH, SHA_ID, to_vec(&"a".repeat(200)).unwrap()),
+            (FAIL_PATH, FAIL_ID, vec![]),
+        ];
+        for (path, id, input) in guests {
+            let elf = std::fs::read(path).unwrap();
+            let emulated = execute(&elf, id, &input, false);
+            let expected = execute(&elf, id, &input, true);
+            assert_eq!(emulated.journal, expected.journal);
+            assert_eq!(emulated.stdout, expected.stdout);
+            assert_eq!(emulated.stderr, expected.stderr);
+            assert_eq!(emulated.cycles, expected.cycles);
+            assert_eq!(emulated.po2, expected.po2);
+            assert_eq!(emulated.exit_status, expected.exit_status);
+        }
+    }
+
+    #[test]
+    fn prove_trace() {
+        use super::Trace;
+        use crate::serde::from_slice;
+
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+        let opts = || backend_opts(Backend::Rust).with_seed(7);
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts()).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let trace = prover.trace().unwrap();
+        let saved = to_vec(&trace).unwrap();
+
+        // Prove the saved trace with a prover that never ran the guest.
+        let trace: Trace = from_slice(&saved).unwrap();
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts()).unwrap();
+        let receipt = prover.prove_trace(&trace).unwrap();
+        receipt.verify(IO_ID).unwrap();
+        assert_eq!(receipt.get_journal_vec().unwrap(), trace.journal());
+        assert_eq!(receipt.inspect().unwrap().po2, trace.po2());
+
+        // With the same seed, this is exactly the receipt of a full run.
+        let mut prover = Prover::new_with_opts(&elf, IO_ID, opts()).unwrap();
+        prover.add_input_u32_slice(&input).unwrap();
+        let run = prover.run().unwrap();
+        assert_eq!(receipt.get_journal().unwrap(), run.get_journal().unwrap());
+        assert_eq!(receipt.get_seal().unwrap(), run.get_seal().unwrap());
+    }
+
+    #[test]
+    fn trace_sink() {
+        let mut events = Vec::new();
+        let opts = ProverOpts::default()
+            .with_backend(Backend::Rust)
+            .with_skip_seal(true)
+            .with_trace_sink(&mut events);
+        run_memio_with_opts(&[(HEAP.start(), 1)], opts).unwrap();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, TraceEvent::Instruction { kind: "SW", .. })));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            TraceEvent::MemoryWrite { addr, value: 1, .. } if *addr == HEAP.start() as u32
+        )));
+
+        let mut profiler = Profiler::new(&std::fs::read(IO_PATH).unwrap()).unwrap();
+        for event in events.iter() {
+            profiler.on_event(event);
+        }
+        let stacks = profiler.folded_stacks();
+        assert!(stacks.keys().any(|stack| stack.contains("main")));
+    }
+
+    #[test]
+    fn gdb_server() {
+        use std::{
+            io::{Cursor, Write},
+            sync::{Arc, Mutex},
+        };
+
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Output {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // QStartNoAckMode, then read the registers and continue to the end.
+        let input = b"$QStartNoAckMode#b0$g#67$c#63".to_vec();
+        let output = Output::default();
+        let server = GdbServer::new(Cursor::new(input), output.clone());
+        let opts = backend_opts(Backend::Rust).with_gdb_server(server);
+        let receipt = run_memio_with_opts(&[(HEAP.start(), 1)], opts).unwrap();
+        assert!(receipt.get_seal().unwrap().is_empty());
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.starts_with("+$OK#9a$"));
+        assert!(output.ends_with("$W00#b7"));
+    }
+
+    #[test]
+    fn ffi_rejects_rust_only_features() {
+        use super::CancellationToken;
+        use risc0_zkp::params::ProofParams;
+
+        let elf = std::fs::read(IO_PATH).unwrap();
+        let input = [1, HEAP.start() as u32, 0];
+        let prover = |opts: ProverOpts<'static>| {
+            let opts = opts.with_backend(Backend::Ffi);
+            let mut prover = Prover::new_with_opts(&elf, IO_ID, opts).unwrap();
+            prover.add_input_u32_slice(&input).unwrap();
+            prover
+        };
+
+        let cheap = ProofParams::default().with_queries(25);
+        assert!(prover(ProverOpts::default().with_proof_params(cheap))
+            .run()
+            .is_err());
+        assert!(prover(ProverOpts::default().with_seed(1)).run().is_err());
+        let progress = ProverOpts::default().with_progress_callback(|_| ());
+        assert!(prover(progress).run().is_err());
+        let token = ProverOpts::default().with_cancellation_token(CancellationToken::new());
+        assert!(prover(token).run().is_err());
+        let sink = ProverOpts::default().with_trace_sink(Vec::<TraceEvent>::new());
+        assert!(prover(sink).run().is_err());
+        let gdb = GdbServer::new(std::io::empty(), std::io::sink());
+        assert!(prover(ProverOpts::default().with_gdb_server(gdb))
+            .run()
+            .is_err());
+        assert!(prover(ProverOpts::default()).execute().is_err());
+        assert!(prover(ProverOpts::default()).trace().is_err());
+
+        let receipt = prover(ProverOpts::default()).run().unwrap();
+        assert_eq!(receipt.backend(), Backend::Ffi);
+        assert!(receipt.inspect().is_err());
+        assert!(receipt.verify_with_min_security(IO_ID, 50).is_err());
+
+        // Guests can only verify receipts of the Rust prover.
+        assert!(run_verify(Backend::Rust, IO_ID, &receipt).is_err());
+    }
+
+    fn run_verify(backend: Backend, method_id: &[u8], receipt: &Receipt) -> Result<Receipt> {
+        let method_id: Vec<u32> = method_id
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let elf = std::fs::read(VERIFY_PATH).unwrap();
+        let mut prover = Prover::new_with_opts(&elf, VERIFY_ID, backend_opts(backend)).unwrap();
+        prover
+            .add_input_u32_slice(&to_vec(&method_id).unwrap())
+            .unwrap();
+        prover.add_receipt(receipt)?;
+        prover.run()
+    }
+
+    fn verify_in_guest(backend: Backend) {
+        // The guest verifier only accepts seals of the Rust prover, but any
+        // backend can prove the guest running it.
+        let inner: Receipt =
+            run_memio_with_opts(&[(HEAP.start(), 0)], backend_opts(Backend::Rust)).unwrap();
+        let outer = run_verify(backend, IO_ID, &inner).unwrap();
+        outer.verify(VERIFY_ID).unwrap();
+
+        let claim: ReceiptClaim = outer.decode_journal().unwrap();
+        let expected = ReceiptClaim::new(
+            default_implementation(),
+            IO_ID,
+            inner.get_journal().unwrap(),
+        );
+        assert_eq!(claim, expected);
+
+        // A receipt checked against the wrong method ID faults the guest.
+        assert!(run_verify(backend, SHA_ID, &inner).is_err());
+    }
+
+    fn fail(backend: Backend) {
         // Check that a compliant host will fault.
-        let prover = Prover::new(&std::fs::read(FAIL_PATH).unwrap(), FAIL_ID).unwrap();
-        assert!(prover.run().is_err());
+        let elf = std::fs::read(FAIL_PATH).unwrap();
+        let result = Prover::new_with_opts(&elf, FAIL_ID, backend_opts(backend))
+            .unwrap()
+            .run();
+        match ProverError::find(&result.err().unwrap()) {
+            Some(ProverError::GuestPanic(msg)) => assert!(msg.contains("Failure")),
+            other => panic!("Unexpected error: {other:?}"),
+        }
     }
 
     #[test]
@@ -221,8 +1116,7 @@ mod test {
         assert!(method_id == clone);
     }
 
-    #[test]
-    fn host_sendrecv() {
+    fn host_sendrecv(backend: Backend) {
         let expected: Vec<Vec<u8>> = vec![
             "".into(),
             "H".into(),
@@ -232,7 +1126,7 @@ mod test {
             "Hello".into(),
         ];
         let actual: Mutex<Vec<Vec<u8>>> = Vec::new().into();
-        let opts = ProverOpts::default()
+        let opts = backend_opts(backend)
             .with_skip_seal(true)
             .with_sendrecv_callback(5, |channel_id, buf| -> Vec<u8> {
                 assert_eq!(channel_id, 5);
@@ -243,17 +1137,17 @@ mod test {
         let mut prover =
             Prover::new_with_opts(&std::fs::read(SENDRECV_PATH).unwrap(), SENDRECV_ID, opts)
                 .unwrap();
-        prover.add_input_u32_slice(&[5, expected.len() as u32 - 1]);
+        prover
+            .add_input_u32_slice(&[5, expected.len() as u32 - 1])
+            .unwrap();
         prover.run().unwrap();
 
         assert_eq!(*actual.lock().unwrap(), expected[..expected.len() - 1]);
     }
 
     // Make sure panics in the callback get propagated correctly.
-    #[test]
-    #[should_panic(expected = "I am panicking from here!")]
-    fn host_sendrecv_callback_panic() {
-        let opts = ProverOpts::default()
+    fn host_sendrecv_callback_panic(backend: Backend) {
+        let opts = backend_opts(backend)
             .with_skip_seal(true)
             .with_sendrecv_callback(5, |_channel_id, _buf| -> Vec<u8> {
                 panic!("I am panicking from here!");
@@ -261,7 +1155,89 @@ mod test {
         let mut prover =
             Prover::new_with_opts(&std::fs::read(SENDRECV_PATH).unwrap(), SENDRECV_ID, opts)
                 .unwrap();
-        prover.add_input_u32_slice(&[5, 5]);
-        prover.run().unwrap();
+        prover.add_input_u32_slice(&[5, 5]).unwrap();
+        assert_eq!(
+            ProverError::find(&prover.run().err().unwrap()),
+            Some(ProverError::CallbackFailed {
+                channel: 5,
+                msg: "I am panicking from here!".into()
+            })
+        );
+    }
+
+    // Replies to each request with the request and one more byte, and fails
+    // once it has served `limit` requests.
+    struct Appender {
+        limit: usize,
+        requests: Vec<Vec<u8>>,
+        cycles: Vec<Option<u32>>,
+    }
+
+    impl SendRecvHandler for Appender {
+        fn handle(&mut self, channel: u32, cycle: Option<u32>, buf: &[u8]) -> Result<Vec<u8>> {
+            assert_eq!(channel, 5);
+            if self.requests.len() == self.limit {
+                anyhow::bail!("Out of replies");
+            }
+            self.requests.push(buf.into());
+            self.cycles.push(cycle);
+            let mut reply = buf.to_vec();
+            reply.push(b'x');
+            Ok(reply)
+        }
+    }
+
+    fn host_sendrecv_handler(backend: Backend) {
+        let elf = std::fs::read(SENDRECV_PATH).unwrap();
+        let run = |handler: &mut Appender| {
+            let opts = backend_opts(backend)
+                .with_skip_seal(true)
+                .with_sendrecv_handler(5, handler);
+            let mut prover = Prover::new_with_opts(&elf, SENDRECV_ID, opts).unwrap();
+            prover.add_input_u32_slice(&[5, 3]).unwrap();
+            prover.run()
+        };
+
+        let mut handler = Appender {
+            limit: 3,
+            requests: Vec::new(),
+            cycles: Vec::new(),
+        };
+        run(&mut handler).unwrap();
+        let expected: Vec<Vec<u8>> = vec!["".into(), "x".into(), "xx".into()];
+        assert_eq!(handler.requests, expected);
+        // Only the Rust prover knows the cycle of each request.
+        match backend {
+            Backend::Ffi => assert!(handler.cycles.iter().all(Option::is_none)),
+            Backend::Rust => {
+                assert!(handler.cycles.iter().all(Option::is_some));
+                assert!(handler.cycles.windows(2).all(|pair| pair[0] < pair[1]));
+            }
+        }
+
+        let mut handler = Appender {
+            limit: 1,
+            requests: Vec::new(),
+            cycles: Vec::new(),
+        };
+        assert_eq!(
+            ProverError::find(&run(&mut handler).err().unwrap()),
+            Some(ProverError::CallbackFailed {
+                channel: 5,
+                msg: "Out of replies".into()
+            })
+        );
+    }
+
+    fn host_sendrecv_unknown_channel(backend: Backend) {
+        let opts = backend_opts(backend).with_skip_seal(true);
+        let mut prover =
+            Prover::new_with_opts(&std::fs::read(SENDRECV_PATH).unwrap(), SENDRECV_ID, opts)
+                .unwrap();
+        prover.add_input_u32_slice(&[7, 1]).unwrap();
+        assert_eq!(
+            ProverError::find(&prover.run().err().unwrap()),
+            Some(ProverError::UnknownChannel(7))
+        );
     }
 }
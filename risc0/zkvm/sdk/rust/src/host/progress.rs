@@ -0,0 +1,62 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use risc0_zkp::prove::ProvePhase;
+
+/// The progress of a [Prover::run](crate::host::Prover::run), as reported to
+/// the callback set with
+/// [ProverOpts::with_progress_callback](crate::host::ProverOpts::with_progress_callback).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProverProgress {
+    /// The phase in progress. [ProvePhase::Execute] is reported repeatedly
+    /// while the guest runs.
+    pub phase: ProvePhase,
+    /// The number of cycles executed so far. After the [ProvePhase::Execute]
+    /// phase this is the total number of cycles of the execution.
+    pub cycles: usize,
+}
+
+/// The callback set with
+/// [ProverOpts::with_progress_callback](crate::host::ProverOpts::with_progress_callback).
+pub(crate) type ProgressCallback<'a> = Box<dyn Fn(&ProverProgress) + 'a + Sync>;
+
+/// A token that cancels the [Prover::run](crate::host::Prover::run) it was
+/// given to with
+/// [ProverOpts::with_cancellation_token](crate::host::ProverOpts::with_cancellation_token).
+/// Clones share the same state, so one can be kept to cancel the run from
+/// another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests that the run stop at the next opportunity.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true once [CancellationToken::cancel] has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
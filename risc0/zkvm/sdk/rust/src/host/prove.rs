@@ -1,4 +1,159 @@
-pub use crate::host::ProverOpts;
-pub use crate::method_id::MethodId;
-pub use crate::prove::Prover;
-pub use crate::receipt::Receipt;
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, Result};
+
+use super::{ffi, Backend, ProverOpts, Receipt};
+use crate::{
+    prove::{SessionSummary, Trace},
+    serde::to_vec,
+};
+
+/// An implementation of the prover, selected with
+/// [ProverOpts::with_backend]. The [Prover] forwards every call to one.
+pub trait ProverBackend {
+    /// Returns which [Backend] this is.
+    fn backend(&self) -> Backend;
+
+    /// Provides private input data that is available to guest-side method
+    /// code to 'read'. The bytes are padded with zeros to a whole number of
+    /// words.
+    fn add_input_u8_slice(&mut self, slice: &[u8]) -> Result<()>;
+
+    /// Provides private input data that is available to guest-side method
+    /// code to 'read'.
+    fn add_input_u32_slice(&mut self, slice: &[u32]) -> Result<()>;
+
+    /// Provides access to private output data written by guest-side method
+    /// code.
+    fn get_output(&self) -> Result<&[u8]>;
+
+    /// Executes the guest and proves it, producing a [Receipt].
+    fn run(&mut self) -> Result<Receipt>;
+
+    /// See [Prover::execute].
+    fn execute(&mut self) -> Result<SessionSummary> {
+        unsupported("Executing without proving", self.backend())
+    }
+
+    /// See [Prover::trace].
+    fn trace(&mut self) -> Result<Trace> {
+        unsupported("Saving execution traces", self.backend())
+    }
+
+    /// See [Prover::prove_trace].
+    fn prove_trace(&mut self, _trace: &Trace) -> Result<Receipt> {
+        unsupported("Proving saved traces", self.backend())
+    }
+}
+
+fn unsupported<T>(what: &str, backend: Backend) -> Result<T> {
+    bail!("{what} is only supported by the Rust prover, not the {backend:?} backend")
+}
+
+/// The prover generates a [Receipt] by executing a given method in a ZKVM,
+/// using the [Backend] chosen by its [ProverOpts].
+pub struct Prover<'a> {
+    inner: Box<dyn ProverBackend + 'a>,
+}
+
+impl<'a> Prover<'a> {
+    /// Create a new [Prover] with the given method (specified via
+    /// `elf_contents`) and an associated method ID (specified via
+    /// `method_id`).
+    pub fn new(elf_contents: &[u8], method_id: &[u8]) -> Result<Self> {
+        Self::new_with_opts(elf_contents, method_id, ProverOpts::default())
+    }
+
+    /// Create a new [Prover] with the given method (specified via
+    /// `elf_contents`) and an associated method ID (specified via
+    /// `method_id`) and additional options.
+    pub fn new_with_opts(
+        elf_contents: &[u8],
+        method_id: &[u8],
+        opts: ProverOpts<'a>,
+    ) -> Result<Self> {
+        let inner: Box<dyn ProverBackend + 'a> = match opts.backend {
+            Backend::Ffi => Box::new(ffi::Prover::new_with_opts(elf_contents, method_id, opts)?),
+            Backend::Rust => Box::new(crate::prove::Prover::new_with_opts(
+                elf_contents,
+                method_id,
+                opts,
+            )?),
+        };
+        Ok(Prover { inner })
+    }
+
+    /// Returns the [Backend] this prover runs on.
+    pub fn backend(&self) -> Backend {
+        self.inner.backend()
+    }
+
+    /// Provide private input data that is available to guest-side method
+    /// code to 'read'. The bytes are padded with zeros to a whole number of
+    /// words.
+    pub fn add_input_u8_slice(&mut self, slice: &[u8]) -> Result<()> {
+        self.inner.add_input_u8_slice(slice)
+    }
+
+    /// Provide private input data that is available to guest-side method
+    /// code to 'read'.
+    pub fn add_input_u32_slice(&mut self, slice: &[u32]) -> Result<()> {
+        self.inner.add_input_u32_slice(slice)
+    }
+
+    /// Supplies an inner [Receipt] to the guest, to be read with
+    /// `risc0_zkvm_guest::env::read_receipt` or verified with
+    /// `risc0_zkvm_guest::env::verify_receipt`. Guests can only verify
+    /// receipts of the Rust prover.
+    pub fn add_receipt(&mut self, receipt: &Receipt) -> Result<()> {
+        if receipt.backend() != Backend::Rust {
+            bail!("Guests can only verify receipts of the Rust prover");
+        }
+        self.add_input_u32_slice(&to_vec(&receipt.get_journal_vec()?)?)?;
+        self.add_input_u32_slice(&to_vec(receipt.get_seal()?)?)
+    }
+
+    /// Provide access to private output data written by guest-side method code.
+    pub fn get_output(&self) -> Result<&[u8]> {
+        self.inner.get_output()
+    }
+
+    /// Execute the ZKVM to produce a [Receipt].
+    pub fn run(&mut self) -> Result<Receipt> {
+        self.inner.run()
+    }
+
+    /// Runs the guest without proving it, returning a summary of the
+    /// execution. Unlike [ProverOpts::with_skip_seal], none of the structures
+    /// needed for a proof are built once execution finishes, and a guest fault
+    /// is reported in the summary rather than as an error. Only supported by
+    /// the Rust prover.
+    pub fn execute(&mut self) -> Result<SessionSummary> {
+        self.inner.execute()
+    }
+
+    /// Runs the guest and returns its execution [Trace], to be proven later
+    /// with [Prover::prove_trace]. Only supported by the Rust prover.
+    pub fn trace(&mut self) -> Result<Trace> {
+        self.inner.trace()
+    }
+
+    /// Proves a [Trace] saved by [Prover::trace], possibly on another machine,
+    /// without running the guest again. Only the options and the method ID of
+    /// this prover are used. Only supported by the Rust prover.
+    pub fn prove_trace(&mut self, trace: &Trace) -> Result<Receipt> {
+        self.inner.prove_trace(trace)
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{bail, Result};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{ffi, Backend, MethodDigest, MethodId};
+use crate::receipt::{IntoMethodId, ReceiptInfo};
+
+/// A record attesting to the correct execution of a 'method'.
+///
+/// Consists of:
+/// * journal: all data the method wants to publicly output and commit to.
+/// * seal: the cryptographic blob which proves that the receipt is valid.
+///
+/// A receipt remembers the [Backend] that produced its seal, since only the
+/// verifier of that backend can check it. Both the serde encoding and a
+/// [ReceiptEnvelope](crate::envelope::ReceiptEnvelope) record it, while the
+/// older encoding without it still decodes through [Receipt::from_words].
+pub struct Receipt {
+    inner: crate::receipt::Receipt,
+    backend: Backend,
+}
+
+impl Receipt {
+    /// Construct a new [Receipt] from individual journal and seal parts,
+    /// produced by the default [Backend].
+    pub fn new(journal: &[u8], seal: &[u32]) -> Result<Self> {
+        Self::new_with_backend(journal, seal, Backend::default())
+    }
+
+    /// Construct a new [Receipt] from individual journal and seal parts,
+    /// produced by the given [Backend].
+    pub fn new_with_backend(journal: &[u8], seal: &[u32], backend: Backend) -> Result<Self> {
+        Ok(Receipt {
+            inner: crate::receipt::Receipt::new(journal, seal)?,
+            backend,
+        })
+    }
+
+    /// Returns the [Backend] that produced the seal.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Verify that the current [Receipt] is a valid result of executing the
+    /// method associated with the given method ID in a ZKVM.
+    pub fn verify<M: IntoMethodId>(&self, method_id: M) -> Result<()> {
+        match self.backend {
+            Backend::Ffi => {
+                let method_id = method_id.into_method_id()?;
+                let receipt = ffi::Receipt::new(self.get_journal()?, self.get_seal()?)?;
+                Ok(receipt.verify(method_id.as_slice()?)?)
+            }
+            Backend::Rust => self.inner.verify(method_id),
+        }
+    }
+
+    /// Verifies the receipt against a [MethodDigest], taking the code roots
+    /// from `table`, a [MethodId] in the [MethodId::as_slice] format, once it
+    /// has been checked against the digest.
+    pub fn verify_with_digest(&self, digest: &MethodDigest, table: &[u8]) -> Result<()> {
+        self.verify(&MethodId::from_digest(digest, table)?)
+    }
+
+    /// Verifies the receipt, accepting seals produced with
+    /// [ProofParams](risc0_zkp::params::ProofParams) that provide at least
+    /// `min_security_bits` of conjectured security. Only supported for
+    /// receipts of the Rust prover.
+    pub fn verify_with_min_security<M: IntoMethodId>(
+        &self,
+        method_id: M,
+        min_security_bits: usize,
+    ) -> Result<()> {
+        self.rust_only("Verifying with a minimum security")?;
+        self.inner
+            .verify_with_min_security(method_id, min_security_bits)
+    }
+
+    /// Returns the size and layout of the seal without verifying it. Only
+    /// supported for receipts of the Rust prover.
+    pub fn inspect(&self) -> Result<ReceiptInfo> {
+        self.rust_only("Inspecting seals")?;
+        self.inner.inspect()
+    }
+
+    /// Provides access to the `seal` of a [Receipt].
+    pub fn get_seal(&self) -> Result<&[u32]> {
+        self.inner.get_seal()
+    }
+
+    /// Provides access to the `journal` of a [Receipt].
+    pub fn get_journal(&self) -> Result<&[u8]> {
+        self.inner.get_journal()
+    }
+
+    /// Provides access to the `journal` of a [Receipt] as a [`Vec<u32>`].
+    pub fn get_journal_vec(&self) -> Result<Vec<u32>> {
+        self.inner.get_journal_vec()
+    }
+
+    fn rust_only(&self, what: &str) -> Result<()> {
+        if self.backend != Backend::Rust {
+            bail!("{what} is only supported for receipts of the Rust prover");
+        }
+        Ok(())
+    }
+}
+
+impl Receipt {
+    pub(crate) fn from_inner(inner: crate::receipt::Receipt, backend: Backend) -> Self {
+        Receipt { inner, backend }
+    }
+}
+
+impl From<crate::receipt::Receipt> for Receipt {
+    fn from(inner: crate::receipt::Receipt) -> Self {
+        Receipt::from_inner(inner, Backend::Rust)
+    }
+}
+
+impl Serialize for Receipt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        (&self.inner, self.backend as u32).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Receipt {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (inner, backend) = <(crate::receipt::Receipt, u32)>::deserialize(deserializer)?;
+        Ok(Receipt {
+            inner,
+            backend: Backend::try_from(backend).map_err(D::Error::custom)?,
+        })
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+/// Serves the sendrecv requests a guest makes on a channel, as registered
+/// with [ProverOpts::with_sendrecv_handler](super::ProverOpts::with_sendrecv_handler).
+///
+/// Unlike a callback, a handler can keep state between requests, such as a
+/// key-value store the guest queries, and can fail the run by returning an
+/// error, which is reported as [ProverError::CallbackFailed](super::ProverError::CallbackFailed).
+pub trait SendRecvHandler {
+    /// Handles the request `buf` the guest sent on `channel`, returning the
+    /// reply to write into the guest's input region. `cycle` is the cycle at
+    /// which the guest made the request, which the FFI-based prover does not
+    /// report.
+    fn handle(&mut self, channel: u32, cycle: Option<u32>, buf: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Lets the caller keep a handler, to inspect its state once the run is
+/// done.
+impl<H: SendRecvHandler + ?Sized> SendRecvHandler for &mut H {
+    fn handle(&mut self, channel: u32, cycle: Option<u32>, buf: &[u8]) -> Result<Vec<u8>> {
+        (**self).handle(channel, cycle, buf)
+    }
+}
+
+/// Adapts a stateless callback, as passed to
+/// [ProverOpts::with_sendrecv_callback](super::ProverOpts::with_sendrecv_callback),
+/// to a [SendRecvHandler].
+pub(crate) struct Callback<F>(pub F);
+
+impl<F> SendRecvHandler for Callback<F>
+where
+    F: Fn(u32, &[u8]) -> Vec<u8>,
+{
+    fn handle(&mut self, channel: u32, _cycle: Option<u32>, buf: &[u8]) -> Result<Vec<u8>> {
+        Ok((self.0)(channel, buf))
+    }
+}
@@ -38,13 +38,14 @@ extern crate alloc;
 pub mod core;
 #[cfg(feature = "prove")]
 pub mod elf;
+pub mod envelope;
 #[cfg(feature = "host")]
 pub mod host;
-#[cfg(feature = "host")]
+#[cfg(any(feature = "host", feature = "verify"))]
 pub mod method_id;
 #[cfg(feature = "prove")]
 pub mod prove;
-#[cfg(feature = "host")]
+#[cfg(any(feature = "host", feature = "verify"))]
 pub mod receipt;
 pub mod serde;
 #[cfg(feature = "verify")]
@@ -14,27 +14,135 @@
 
 extern crate alloc;
 
-use alloc::vec::Vec;
-use anyhow::Result;
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::{bail, Result};
 use risc0_zkp::{
     core::{
         fp::Fp,
         log2_ceil,
-        sha::{Digest, DIGEST_WORDS, DIGEST_WORD_SIZE},
+        sha::{default_implementation, Digest, Sha, DIGEST_WORDS, DIGEST_WORD_SIZE},
     },
+    params::ProofParams,
     MAX_CYCLES, MIN_CYCLES, ZK_CYCLES,
 };
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// The default digest count when generating a MethodId.
 pub const DEFAULT_METHOD_ID_LIMIT: u32 = 12;
 
-const MAX_CODE_DIGEST_COUNT: u32 = (log2_ceil(MAX_CYCLES / MIN_CYCLES) + 1) as _;
+/// The largest useful digest count when generating a MethodId: there are no
+/// more supported trace sizes beyond it.
+pub const MAX_CODE_DIGEST_COUNT: u32 = (log2_ceil(MAX_CYCLES / MIN_CYCLES) + 1) as _;
+
+const DIGEST_BYTES: usize = DIGEST_WORDS * DIGEST_WORD_SIZE;
 
+/// A table of code roots, one for each supported trace size, identifying a
+/// method.
+///
+/// A MethodId is written as the hex encoding of its bytes in the
+/// [MethodId::as_slice] format, by [Display] and parsed back by [FromStr]. It
+/// is also serialized that way by human-readable serde formats such as JSON,
+/// and as its table of digests by the others. [MethodIdList] gives an
+/// alternative encoding that lists each code root separately, along with the
+/// limit.
 #[derive(Clone, Eq, PartialEq)]
 pub struct MethodId {
     pub table: Vec<Digest>,
 }
 
+/// Errors produced while parsing the encodings of a [MethodId].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MethodIdError {
+    /// The encoding has the given number of hex digits, which is not a whole
+    /// number of digests.
+    InvalidLength(usize),
+    /// The character at the given position is not a hex digit.
+    InvalidHexDigit(usize),
+    /// The number of digests does not match the limit they were computed with.
+    LimitMismatch { limit: u32, digests: usize },
+}
+
+impl Display for MethodIdError {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "MethodId must be a multiple of {} hex digits, got {len}",
+                DIGEST_BYTES * 2
+            ),
+            Self::InvalidHexDigit(pos) => write!(f, "Invalid hex digit at position {pos}"),
+            Self::LimitMismatch { limit, digests } => {
+                write!(f, "MethodId with limit {limit} has {digests} digests")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MethodIdError {}
+
+/// The digest-list encoding of a [MethodId], which records the limit it was
+/// computed with and each code root in hex. In JSON:
+///
+/// ```json
+/// {
+///   "limit": 12,
+///   "digests": ["4f9e...", "..."]
+/// }
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MethodIdList {
+    pub limit: u32,
+    pub digests: Vec<String>,
+}
+
+/// A compact identity for a [MethodId]: the SHA-256 digest of its complete
+/// table of code roots, one for each of the [MAX_CODE_DIGEST_COUNT] supported
+/// trace sizes, in the [MethodId::as_slice] format.
+///
+/// Every limit of at least [MAX_CODE_DIGEST_COUNT] gives the same complete
+/// table, and so the same digest. A table computed with a smaller limit is
+/// missing the code roots of the larger trace sizes, and has no digest.
+///
+/// Verifying a receipt still needs the code root for the po2 of its seal, so
+/// the table must be supplied alongside the digest (for example in a
+/// [ReceiptEnvelope](crate::envelope::ReceiptEnvelope)) and checked against it
+/// with [MethodId::from_digest].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MethodDigest(pub Digest);
+
+impl MethodDigest {
+    /// Computes the digest of a complete table of code roots in the
+    /// [MethodId::as_slice] format.
+    pub fn from_table(table: &[u8]) -> Result<Self> {
+        let expected = MAX_CODE_DIGEST_COUNT as usize * DIGEST_BYTES;
+        if table.len() != expected {
+            bail!(
+                "Method digest needs all {} code roots, got a table of {} bytes",
+                MAX_CODE_DIGEST_COUNT,
+                table.len()
+            );
+        }
+        Ok(MethodDigest(*default_implementation().hash_bytes(table)))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self.0.as_slice())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != DIGEST_BYTES {
+            bail!("Method digest must be {} bytes", DIGEST_BYTES);
+        }
+        Ok(MethodDigest(parse_digest(bytes)))
+    }
+}
+
 impl MethodId {
     pub fn as_slice(&self) -> Result<&[u8]> {
         Ok(bytemuck::cast_slice(self.table.as_slice()))
@@ -42,18 +150,56 @@ impl MethodId {
 
     pub fn from_slice(bytes: &[u8]) -> Result<Self> {
         let mut table = Vec::new();
-        for digest in bytes.chunks_exact(DIGEST_WORDS * DIGEST_WORD_SIZE) {
-            let words: Vec<u32> = digest
-                .chunks_exact(DIGEST_WORD_SIZE)
-                .map(|x| {
-                    let mut word = 0;
-                    for i in 0..4 {
-                        word |= (x[i] as u32) << (i * 8);
-                    }
-                    word
-                })
-                .collect();
-            table.push(Digest::try_from_slice(&words)?);
+        for digest in bytes.chunks_exact(DIGEST_BYTES) {
+            table.push(parse_digest(digest));
+        }
+        Ok(MethodId { table })
+    }
+
+    /// Returns the compact [MethodDigest] of this MethodId, which must have
+    /// been computed with a limit of at least [MAX_CODE_DIGEST_COUNT].
+    pub fn digest(&self) -> Result<MethodDigest> {
+        MethodDigest::from_table(self.as_slice()?)
+    }
+
+    /// Loads a table of code roots in the [MethodId::as_slice] format, checking
+    /// that it matches the given [MethodDigest].
+    pub fn from_digest(digest: &MethodDigest, table: &[u8]) -> Result<Self> {
+        if MethodDigest::from_table(table)? != *digest {
+            bail!("MethodId table does not match digest");
+        }
+        MethodId::from_slice(table)
+    }
+
+    /// Returns the [MethodIdList] encoding of this MethodId. The limit is the
+    /// number of digests in the table.
+    pub fn to_list(&self) -> MethodIdList {
+        MethodIdList {
+            limit: self.table.len() as u32,
+            digests: self
+                .table
+                .iter()
+                .map(|digest| encode_hex(bytemuck::cast_slice(digest.as_slice())))
+                .collect(),
+        }
+    }
+
+    /// Loads a MethodId from its [MethodIdList] encoding, checking that it has
+    /// as many digests as are computed for its limit.
+    pub fn from_list(list: &MethodIdList) -> core::result::Result<Self, MethodIdError> {
+        let expected = core::cmp::min(list.limit, MAX_CODE_DIGEST_COUNT) as usize;
+        if list.digests.len() != expected {
+            return Err(MethodIdError::LimitMismatch {
+                limit: list.limit,
+                digests: list.digests.len(),
+            });
+        }
+        let mut table = Vec::new();
+        for digest in list.digests.iter() {
+            if digest.len() != DIGEST_BYTES * 2 {
+                return Err(MethodIdError::InvalidLength(digest.len()));
+            }
+            table.push(parse_digest(&decode_hex(digest)?));
         }
         Ok(MethodId { table })
     }
@@ -65,61 +211,371 @@ impl MethodId {
 
     #[cfg(feature = "prove")]
     pub fn compute_with_limit(elf_contents: &[u8], limit: u32) -> Result<Self> {
+        MethodId::compute_with_params(elf_contents, limit, &ProofParams::DEFAULT)
+    }
+
+    /// Computes the code roots for proofs made with the given [ProofParams].
+    /// Only `inv_rate` affects the result.
+    ///
+    /// The code of each level is a prefix of the code of the largest one, so
+    /// the program is only loaded once and the levels are then committed to in
+    /// parallel.
+    #[cfg(feature = "prove")]
+    pub fn compute_with_params(
+        elf_contents: &[u8],
+        limit: u32,
+        params: &ProofParams,
+    ) -> Result<Self> {
         use crate::{elf::Program, platform::memory::MEM_SIZE, prove::CIRCUIT, CODE_SIZE};
+        use rayon::prelude::*;
         use risc0_zkp::{
             hal::{cpu::CpuHal, Hal},
             prove::poly_group::PolyGroup,
         };
         use risc0_zkvm_circuit::CircuitImpl;
 
-        let hal = CpuHal::<CircuitImpl>::new(&CIRCUIT);
         let program = Program::load_elf(elf_contents, MEM_SIZE as u32)?;
 
-        // Start with an empty table
-        let mut table = Vec::new();
-
         // Make the digest for each level
-        let count = std::cmp::min(limit, MAX_CODE_DIGEST_COUNT);
-        for i in 0..count {
-            let cycles = MIN_CYCLES * (1 << i);
-            if cycles < program.image.len() + 3 + ZK_CYCLES {
+        let count = std::cmp::min(limit, MAX_CODE_DIGEST_COUNT) as usize;
+        let levels: Vec<usize> = (0..count).map(|i| MIN_CYCLES * (1 << i)).collect();
+        let fits = |cycles: usize| cycles >= program.image.len() + 3 + ZK_CYCLES;
+        let trace = match levels.last() {
+            Some(&max_cycles) if fits(max_cycles) => Some(CodeTrace::new(&program, max_cycles)?),
+            _ => None,
+        };
+
+        let table = levels
+            .into_par_iter()
+            .map(|cycles| match &trace {
+                Some(trace) if fits(cycles) => {
+                    let hal = CpuHal::<CircuitImpl>::new(&CIRCUIT);
+                    // Copy into accel buffer
+                    let coeffs = hal.copy_fp_from(&trace.code(cycles));
+                    // Do interpolate & shift
+                    hal.batch_interpolate_ntt(&coeffs, CODE_SIZE);
+                    hal.zk_shift(&coeffs, CODE_SIZE);
+                    // Make the poly-group & extract the root
+                    let code_group = PolyGroup::new(&hal, &coeffs, CODE_SIZE, cycles, params);
+                    *code_group.merkle.root()
+                }
                 // Can't even fit the program in this cycle size, just set to zero
-                table.push(Digest::default());
-                continue;
+                _ => Digest::default(),
+            })
+            .collect();
+
+        Ok(MethodId { table })
+    }
+
+    /// Like [MethodId::compute_with_limit], but keeps the result in
+    /// `cache_dir`, keyed on the SHA-256 digest of the ELF and the limit, so
+    /// that it is only computed once for a given ELF.
+    #[cfg(feature = "prove")]
+    pub fn compute_cached(
+        elf_contents: &[u8],
+        limit: u32,
+        cache_dir: &std::path::Path,
+    ) -> Result<Self> {
+        let elf_sha = default_implementation().hash_bytes(elf_contents);
+        let path = cache_dir.join(format!("{}-{}.id", elf_sha.to_hex(), limit));
+
+        // Ignore unreadable or truncated entries and compute them again
+        let count = std::cmp::min(limit, MAX_CODE_DIGEST_COUNT) as usize;
+        if let Ok(bytes) = std::fs::read(&path) {
+            if bytes.len() == count * DIGEST_WORDS * DIGEST_WORD_SIZE {
+                return MethodId::from_slice(&bytes);
             }
+        }
 
-            // Make a vector & set it up with the elf data
-            let mut code = vec![Fp::default(); cycles * CODE_SIZE];
-            load_code(&mut code, &program, cycles)?;
+        let method_id = MethodId::compute_with_limit(elf_contents, limit)?;
+        // Write to a temporary file first so that concurrent builds never see
+        // a partial entry.
+        std::fs::create_dir_all(cache_dir)?;
+        let tmp_path = path.with_extension(format!("id.{}", std::process::id()));
+        std::fs::write(&tmp_path, method_id.as_slice()?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(method_id)
+    }
+}
 
-            // Copy into accel buffer
-            let coeffs = hal.copy_fp_from(&code);
-            // Do interpolate & shift
-            hal.batch_interpolate_ntt(&coeffs, CODE_SIZE);
-            hal.zk_shift(&coeffs, CODE_SIZE);
-            // Make the poly-group & extract the root
-            let code_group = PolyGroup::new(&hal, &coeffs, CODE_SIZE, cycles);
-            table.push(code_group.merkle.root().clone());
+impl Display for MethodId {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        for byte in bytemuck::cast_slice::<_, u8>(self.table.as_slice()) {
+            write!(f, "{byte:02x}")?;
         }
+        Ok(())
+    }
+}
 
-        Ok(MethodId { table })
+impl FromStr for MethodId {
+    type Err = MethodIdError;
+
+    fn from_str(s: &str) -> core::result::Result<Self, MethodIdError> {
+        if s.len() % (DIGEST_BYTES * 2) != 0 {
+            return Err(MethodIdError::InvalidLength(s.len()));
+        }
+        let bytes = decode_hex(s)?;
+        Ok(MethodId {
+            table: bytes.chunks_exact(DIGEST_BYTES).map(parse_digest).collect(),
+        })
     }
 }
 
-#[cfg(feature = "prove")]
-fn load_code(code: &mut [Fp], elf: &crate::elf::Program, cycles: usize) -> Result<()> {
-    use crate::{prove::exec, CODE_SIZE};
+impl Serialize for MethodId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.table.serialize(serializer)
+        }
+    }
+}
 
-    let mut cycle = 0;
-    exec::load_code(elf.entry, &elf.image, |chunk, fini| {
-        for i in 0..CODE_SIZE {
-            code[cycles * i + cycle] = chunk[i];
+impl<'de> Deserialize<'de> for MethodId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        struct HexVisitor;
+
+        impl<'de> de::Visitor<'de> for HexVisitor {
+            type Value = MethodId;
+
+            fn expecting(&self, f: &mut Formatter) -> core::fmt::Result {
+                write!(f, "a hex encoded MethodId")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> core::result::Result<MethodId, E> {
+                s.parse().map_err(E::custom)
+            }
         }
-        if cycle + fini + ZK_CYCLES < cycles {
-            cycle += 1;
-            Ok(true)
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HexVisitor)
         } else {
-            Ok(false)
+            Ok(MethodId {
+                table: Vec::deserialize(deserializer)?,
+            })
         }
-    })
+    }
+}
+
+fn parse_digest(bytes: &[u8]) -> Digest {
+    let mut words = [0; DIGEST_WORDS];
+    for (word, bytes) in words.iter_mut().zip(bytes.chunks_exact(DIGEST_WORD_SIZE)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    Digest::new(words)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    use core::fmt::Write;
+
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{byte:02x}").unwrap();
+    }
+    hex
+}
+
+fn decode_hex(hex: &str) -> core::result::Result<Vec<u8>, MethodIdError> {
+    let digit = |pos: usize| {
+        (hex.as_bytes()[pos] as char)
+            .to_digit(16)
+            .ok_or(MethodIdError::InvalidHexDigit(pos))
+    };
+    if hex.len() % 2 != 0 {
+        return Err(MethodIdError::InvalidLength(hex.len()));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|pos| Ok((digit(pos)? << 4 | digit(pos + 1)?) as u8))
+        .collect()
+}
+
+/// The rows written by [exec::load_code](crate::prove::exec::load_code) for the
+/// largest level of a [MethodId]. Every smaller level runs the same rows until
+/// it runs out of room and is finalized.
+#[cfg(feature = "prove")]
+struct CodeTrace {
+    // Row-major, CODE_SIZE elements per row.
+    rows: Vec<Fp>,
+}
+
+#[cfg(feature = "prove")]
+impl CodeTrace {
+    fn new(program: &crate::elf::Program, max_cycles: usize) -> Result<Self> {
+        use crate::prove::exec;
+
+        let mut rows = Vec::new();
+        let mut cycle = 0;
+        exec::load_code(program.entry, &program.image, |chunk, fini| {
+            if cycle + fini + ZK_CYCLES < max_cycles {
+                rows.extend_from_slice(chunk);
+                cycle += 1;
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        })?;
+        Ok(CodeTrace { rows })
+    }
+
+    /// Returns the column-major code for a level with the given number of
+    /// cycles.
+    fn code(&self, cycles: usize) -> Vec<Fp> {
+        use crate::{prove::exec, CODE_SIZE};
+
+        let fini_cycle = cycles - 1 - ZK_CYCLES;
+        let fini = exec::fini_code(fini_cycle + 1);
+        let rows = self.rows.chunks_exact(CODE_SIZE).take(fini_cycle);
+        let mut code = vec![Fp::default(); cycles * CODE_SIZE];
+        for (cycle, row) in rows.chain([&fini[..]]).enumerate() {
+            for i in 0..CODE_SIZE {
+                code[cycles * i + cycle] = row[i];
+            }
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use risc0_zkp::core::sha::Digest;
+
+    use super::{MethodDigest, MethodId, MethodIdError, MethodIdList, MAX_CODE_DIGEST_COUNT};
+
+    #[test]
+    fn method_digest() {
+        let method_id = MethodId {
+            table: (0..MAX_CODE_DIGEST_COUNT)
+                .map(|i| Digest::new([i; 8]))
+                .collect(),
+        };
+        let table = method_id.as_slice().unwrap();
+        let digest = method_id.digest().unwrap();
+        assert_eq!(digest, MethodDigest::from_table(table).unwrap());
+        assert_eq!(MethodDigest::from_bytes(digest.as_bytes()).unwrap(), digest);
+        assert!(MethodDigest::from_bytes(&table[..31]).is_err());
+
+        let loaded = MethodId::from_digest(&digest, table).unwrap();
+        assert!(loaded == method_id);
+
+        let mut tampered = table.to_vec();
+        tampered[0] ^= 1;
+        assert!(MethodId::from_digest(&digest, &tampered).is_err());
+        assert!(MethodId::from_digest(&digest, &table[32..]).is_err());
+
+        // A table computed with a smaller limit lacks the larger code roots.
+        let partial = MethodId {
+            table: method_id.table[..10].to_vec(),
+        };
+        assert!(partial.digest().is_err());
+    }
+
+    #[test]
+    fn hex_encoding() {
+        let method_id = MethodId {
+            table: (0..3).map(|i| Digest::new([i << 24 | 0xab; 8])).collect(),
+        };
+        let hex = method_id.to_string();
+        assert_eq!(hex.len(), 3 * 64);
+        assert!(hex.starts_with("ab000000ab000000"));
+        assert!(hex.parse::<MethodId>().unwrap() == method_id);
+        assert!(hex.to_uppercase().parse::<MethodId>().unwrap() == method_id);
+
+        assert_eq!(
+            hex[..100].parse::<MethodId>().err(),
+            Some(MethodIdError::InvalidLength(100))
+        );
+        let mut bad = hex.clone();
+        bad.replace_range(70..71, "g");
+        assert_eq!(
+            bad.parse::<MethodId>().err(),
+            Some(MethodIdError::InvalidHexDigit(70))
+        );
+    }
+
+    #[test]
+    fn serde_encoding() {
+        let method_id = MethodId {
+            table: (0..2).map(|i| Digest::new([i; 8])).collect(),
+        };
+        let json = serde_json::to_string(&method_id).unwrap();
+        assert_eq!(json, format!("\"{}\"", method_id));
+        assert!(serde_json::from_str::<MethodId>(&json).unwrap() == method_id);
+        assert!(serde_json::from_str::<MethodId>("\"abc\"").is_err());
+
+        let words = crate::serde::to_vec(&method_id).unwrap();
+        assert!(crate::serde::from_slice::<MethodId>(&words).unwrap() == method_id);
+    }
+
+    #[test]
+    fn list_encoding() {
+        let method_id = MethodId {
+            table: (0..4).map(|i| Digest::new([i; 8])).collect(),
+        };
+        let list = method_id.to_list();
+        assert_eq!(list.limit, 4);
+        assert_eq!(list.digests[1], "01000000".repeat(8));
+        assert!(MethodId::from_list(&list).unwrap() == method_id);
+
+        let json = serde_json::to_string(&list).unwrap();
+        let parsed: MethodIdList = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, list);
+
+        let mut wrong_limit = list.clone();
+        wrong_limit.limit = 5;
+        assert_eq!(
+            MethodId::from_list(&wrong_limit).err(),
+            Some(MethodIdError::LimitMismatch {
+                limit: 5,
+                digests: 4
+            })
+        );
+        let mut short = list;
+        short.digests[2].truncate(62);
+        assert_eq!(
+            MethodId::from_list(&short).err(),
+            Some(MethodIdError::InvalidLength(62))
+        );
+    }
+
+    #[cfg(feature = "prove")]
+    #[test]
+    fn code_trace() {
+        use std::collections::BTreeMap;
+
+        use risc0_zkp::{core::fp::Fp, ZK_CYCLES};
+
+        use super::CodeTrace;
+        use crate::{elf::Program, prove::exec, CODE_SIZE};
+
+        // Loads the code of a single level directly.
+        fn load_code(program: &Program, cycles: usize) -> Vec<Fp> {
+            let mut code = vec![Fp::default(); cycles * CODE_SIZE];
+            let mut cycle = 0;
+            exec::load_code(program.entry, &program.image, |chunk, fini| {
+                for i in 0..CODE_SIZE {
+                    code[cycles * i + cycle] = chunk[i];
+                }
+                if cycle + fini + ZK_CYCLES < cycles {
+                    cycle += 1;
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            })
+            .unwrap();
+            code
+        }
+
+        let program = Program {
+            entry: 0x1000,
+            image: (0..100)
+                .map(|i| (0x1000 + i * 4, i * 7))
+                .collect::<BTreeMap<_, _>>(),
+        };
+        let trace = CodeTrace::new(&program, 4096).unwrap();
+        for cycles in [512, 1024, 2048, 4096] {
+            assert!(trace.code(cycles) == load_code(&program, cycles));
+        }
+    }
 }
@@ -0,0 +1,456 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::max;
+
+use anyhow::{bail, Result};
+use log::debug;
+use risc0_zkp::{core::log2_ceil, MAX_CYCLES_PO2, MIN_PO2, ZK_CYCLES};
+
+use super::exec::{self, IoHandler, MachineContext, INSN_CYCLES, MONITOR_INTERVAL, SHA_CYCLES};
+use crate::{
+    elf::Program,
+    host::ProverError,
+    platform::memory::{MEM_BITS, SHA},
+};
+
+/// Runs a guest on a plain rv32im interpreter instead of the circuit, for
+/// executions that are not proven.
+///
+/// Memory and the guest's I/O go through the same [MachineContext] as in the
+/// [RV32Executor](exec::RV32Executor), and cycles are counted the way the
+/// circuit takes them, so a run gives the same journal, output, cycle count
+/// and trace size. Only the memory the circuit reads without the guest asking
+/// for it, such as address 0 for instructions that do not load, is left
+/// untouched.
+pub struct Emulator<'a, H: IoHandler> {
+    elf: &'a Program,
+    machine: MachineContext<'a, H>,
+    regs: [u32; 32],
+    pc: u32,
+    // The cycle the next instruction is decoded in.
+    cycle: usize,
+    // The first cycle of the body, which the instruction and SHA phases count
+    // from.
+    body_start: usize,
+    // The cycle the circuit halts in, once the guest has halted.
+    halt_cycle: Option<usize>,
+}
+
+impl<'a, H: IoHandler> Emulator<'a, H> {
+    pub fn new(elf: &'a Program, io: &'a mut H) -> Self {
+        // The circuit loads the image after an init cycle, then resets.
+        let body_start = elf.image.len() + 2;
+        Self {
+            elf,
+            machine: MachineContext::new(io).without_history(),
+            regs: [0; 32],
+            pc: elf.entry,
+            cycle: body_start,
+            body_start,
+            halt_cycle: None,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        self.run_with_monitor(|_| Ok(()))
+    }
+
+    /// Like [Emulator::run], but calls `monitor` with the cycles the
+    /// [RV32Executor](exec::RV32Executor) would call its monitor with,
+    /// stopping with its error if it fails.
+    pub fn run_with_monitor<F>(&mut self, mut monitor: F) -> Result<()>
+    where
+        F: FnMut(usize) -> Result<()>,
+    {
+        report(0..self.body_start, &mut monitor)?;
+        for (idx, (addr, data)) in self.elf.image.iter().enumerate() {
+            self.machine.write_word(1 + idx as u32, addr / 4, *data)?;
+        }
+        while self.halt_cycle.is_none() {
+            report(self.cycle..self.cycle + INSN_CYCLES, &mut monitor)?;
+            self.step()?;
+        }
+        report(self.cycle..self.cycles(), &mut monitor)
+    }
+
+    /// The number of cycles the circuit runs the guest for, including loading
+    /// the program and the cycles that pad its trace. If the guest stopped
+    /// with an error, the cycles up to the final cycle of the instruction that
+    /// failed.
+    pub fn cycles(&self) -> usize {
+        match self.halt_cycle {
+            Some(_) => (1 << self.po2()) - ZK_CYCLES,
+            None => self.last_cycle(),
+        }
+    }
+
+    /// The log2 of the trace size a proof of this execution needs, or that
+    /// the circuit had reached if the guest stopped with an error.
+    pub fn po2(&self) -> usize {
+        // The trace needs room for a final cycle after the last one.
+        max(
+            max(exec::min_po2(self.elf), MIN_PO2),
+            log2_ceil(self.last_cycle() + 2 + ZK_CYCLES),
+        )
+    }
+
+    // The cycle the circuit halts in, or the final cycle of the instruction
+    // the guest stopped in.
+    fn last_cycle(&self) -> usize {
+        self.halt_cycle.unwrap_or(self.cycle + INSN_CYCLES - 1)
+    }
+
+    /// Returns the number of bytes of memory the guest has touched, including
+    /// its loaded image.
+    pub fn touched_bytes(&self) -> usize {
+        self.machine.touched_bytes()
+    }
+
+    /// Runs one instruction, in the cycles the circuit decodes, computes and
+    /// finishes it in.
+    fn step(&mut self) -> Result<()> {
+        if self.cycle + INSN_CYCLES + ZK_CYCLES >= 1 << MAX_CYCLES_PO2 {
+            return Err(ProverError::CycleLimitExceeded.into());
+        }
+        let cycle = self.cycle as u32;
+        let pc = self.pc;
+        if pc % 4 != 0 {
+            return Err(ProverError::UnalignedAccess {
+                addr: pc,
+                cycle: Some(cycle),
+            }
+            .into());
+        }
+        let insn = self.machine.read_word(cycle, word_addr(pc, cycle)?);
+
+        let opcode = insn & 0x7f;
+        let rd = ((insn >> 7) & 0x1f) as usize;
+        let funct3 = (insn >> 12) & 0x7;
+        let rs1 = self.regs[((insn >> 15) & 0x1f) as usize];
+        let rs2 = self.regs[((insn >> 20) & 0x1f) as usize];
+        let funct7 = insn >> 25;
+        let imm_i = (insn as i32 >> 20) as u32;
+        let imm_s = ((insn as i32 >> 20) as u32 & !0x1f) | ((insn >> 7) & 0x1f);
+        let imm_b = ((insn as i32 >> 19) as u32 & 0xffff_f000)
+            | ((insn << 4) & 0x800)
+            | ((insn >> 20) & 0x7e0)
+            | ((insn >> 7) & 0x1e);
+        let imm_u = insn & 0xffff_f000;
+        let imm_j = ((insn as i32 >> 11) as u32 & 0xfff0_0000)
+            | (insn & 0xff000)
+            | ((insn >> 9) & 0x800)
+            | ((insn >> 20) & 0x7fe);
+
+        let mut next_pc = pc.wrapping_add(4);
+        let result = match opcode {
+            // LUI
+            0b0110111 => Some(imm_u),
+            // AUIPC
+            0b0010111 => Some(pc.wrapping_add(imm_u)),
+            // JAL
+            0b1101111 => {
+                next_pc = pc.wrapping_add(imm_j);
+                Some(pc.wrapping_add(4))
+            }
+            // JALR
+            0b1100111 if funct3 == 0 => {
+                next_pc = rs1.wrapping_add(imm_i) & !1;
+                Some(pc.wrapping_add(4))
+            }
+            // BEQ, BNE, BLT, BGE, BLTU, BGEU
+            0b1100011 => {
+                let taken = match funct3 {
+                    0 => rs1 == rs2,
+                    1 => rs1 != rs2,
+                    4 => (rs1 as i32) < (rs2 as i32),
+                    5 => (rs1 as i32) >= (rs2 as i32),
+                    6 => rs1 < rs2,
+                    7 => rs1 >= rs2,
+                    _ => return invalid(pc, insn),
+                };
+                if taken {
+                    next_pc = pc.wrapping_add(imm_b);
+                }
+                None
+            }
+            // LB, LH, LW, LBU, LHU
+            0b0000011 => {
+                let addr = rs1.wrapping_add(imm_i);
+                Some(match funct3 {
+                    0 => self.load(addr, 1)? as i8 as u32,
+                    1 => self.load(addr, 2)? as i16 as u32,
+                    2 => self.load(addr, 4)?,
+                    4 => self.load(addr, 1)?,
+                    5 => self.load(addr, 2)?,
+                    _ => return invalid(pc, insn),
+                })
+            }
+            // SB, SH, SW
+            0b0100011 => {
+                let addr = rs1.wrapping_add(imm_s);
+                match funct3 {
+                    0 => self.store(addr, 1, rs2)?,
+                    1 => self.store(addr, 2, rs2)?,
+                    2 => self.store(addr, 4, rs2)?,
+                    _ => return invalid(pc, insn),
+                }
+                None
+            }
+            // ADDI, SLTI, SLTIU, XORI, ORI, ANDI, SLLI, SRLI, SRAI
+            0b0010011 => {
+                let shamt = imm_i & 0x1f;
+                Some(match (funct3, funct7) {
+                    (0, _) => rs1.wrapping_add(imm_i),
+                    (2, _) => ((rs1 as i32) < (imm_i as i32)) as u32,
+                    (3, _) => (rs1 < imm_i) as u32,
+                    (4, _) => rs1 ^ imm_i,
+                    (6, _) => rs1 | imm_i,
+                    (7, _) => rs1 & imm_i,
+                    (1, 0) => rs1 << shamt,
+                    (5, 0) => rs1 >> shamt,
+                    (5, 0x20) => ((rs1 as i32) >> shamt) as u32,
+                    _ => return invalid(pc, insn),
+                })
+            }
+            // The base integer and M extension register operations.
+            0b0110011 => {
+                let shamt = rs2 & 0x1f;
+                Some(match (funct7, funct3) {
+                    (0, 0) => rs1.wrapping_add(rs2),
+                    (0x20, 0) => rs1.wrapping_sub(rs2),
+                    (0, 1) => rs1 << shamt,
+                    (0, 2) => ((rs1 as i32) < (rs2 as i32)) as u32,
+                    (0, 3) => (rs1 < rs2) as u32,
+                    (0, 4) => rs1 ^ rs2,
+                    (0, 5) => rs1 >> shamt,
+                    (0x20, 5) => ((rs1 as i32) >> shamt) as u32,
+                    (0, 6) => rs1 | rs2,
+                    (0, 7) => rs1 & rs2,
+                    (1, 0) => rs1.wrapping_mul(rs2),
+                    (1, 1) => ((rs1 as i32 as i64 * rs2 as i32 as i64) >> 32) as u32,
+                    (1, 2) => ((rs1 as i32 as i64 * rs2 as i64) >> 32) as u32,
+                    (1, 3) => ((rs1 as u64 * rs2 as u64) >> 32) as u32,
+                    (1, 4) if rs2 == 0 => u32::MAX,
+                    (1, 4) => (rs1 as i32).wrapping_div(rs2 as i32) as u32,
+                    (1, 5) if rs2 == 0 => u32::MAX,
+                    (1, 5) => rs1 / rs2,
+                    (1, 6) if rs2 == 0 => rs1,
+                    (1, 6) => (rs1 as i32).wrapping_rem(rs2 as i32) as u32,
+                    (1, 7) if rs2 == 0 => rs1,
+                    (1, 7) => rs1 % rs2,
+                    _ => return invalid(pc, insn),
+                })
+            }
+            // ECALL and EBREAK both halt the guest.
+            0b1110011 if funct3 == 0 && funct7 == 0 => {
+                self.halt()?;
+                None
+            }
+            _ => return invalid(pc, insn),
+        };
+
+        if let Some(value) = result {
+            if rd != 0 {
+                self.regs[rd] = value;
+            }
+        }
+        self.pc = next_pc;
+        self.cycle += INSN_CYCLES;
+        Ok(())
+    }
+
+    /// Loads `size` bytes at `addr`, which the circuit reads in the compute
+    /// cycle.
+    fn load(&mut self, addr: u32, size: u32) -> Result<u32> {
+        let cycle = self.cycle as u32 + 1;
+        let word = self
+            .machine
+            .read_word(cycle, access_addr(addr, size, cycle)?);
+        Ok(extract(word, addr, size))
+    }
+
+    /// Stores the low `size` bytes of `value` at `addr`. The circuit writes in
+    /// the final cycle, after reading the word in the compute cycle for the
+    /// stores that only write part of it.
+    fn store(&mut self, addr: u32, size: u32, value: u32) -> Result<()> {
+        let cycle = self.cycle as u32 + 1;
+        let word_addr = access_addr(addr, size, cycle)?;
+        let data = if size == 4 {
+            value
+        } else {
+            let mask = ((1u64 << (size * 8)) - 1) as u32;
+            let shift = (addr % 4) * 8;
+            let old = self.machine.read_word(cycle, word_addr);
+            old & !(mask << shift) | (value & mask) << shift
+        };
+        self.machine.write_word(cycle + 1, word_addr, data)
+    }
+
+    /// Halts the guest. The circuit waits for the next SHA phase to start, then
+    /// runs the blocks of the first SHA descriptor before halting.
+    fn halt(&mut self) -> Result<()> {
+        let sync_end = self.cycle + 2;
+        let phase = (sync_end - self.body_start) % SHA_CYCLES;
+        let sha_start = if phase == 0 {
+            sync_end
+        } else {
+            sync_end + SHA_CYCLES - phase
+        };
+        let desc = self
+            .machine
+            .read_word(sha_start as u32, SHA.start() as u32 / 4);
+        let blocks = (desc & 0xffff) as usize;
+        let halt_cycle = sha_start + SHA_CYCLES * blocks + 1;
+        if halt_cycle + 1 + ZK_CYCLES >= 1 << MAX_CYCLES_PO2 {
+            return Err(ProverError::CycleLimitExceeded.into());
+        }
+        debug!(
+            "HALT at cycle {}, circuit halts at {halt_cycle}",
+            self.cycle
+        );
+        self.halt_cycle = Some(halt_cycle);
+        Ok(())
+    }
+}
+
+/// Calls `monitor` with each cycle in `cycles` that is a multiple of
+/// [MONITOR_INTERVAL].
+fn report<F>(cycles: std::ops::Range<usize>, monitor: &mut F) -> Result<()>
+where
+    F: FnMut(usize) -> Result<()>,
+{
+    let first = (cycles.start + MONITOR_INTERVAL - 1) / MONITOR_INTERVAL * MONITOR_INTERVAL;
+    for cycle in (first..cycles.end).step_by(MONITOR_INTERVAL) {
+        monitor(cycle)?;
+    }
+    Ok(())
+}
+
+fn invalid<T>(pc: u32, insn: u32) -> Result<T> {
+    bail!("Invalid instruction 0x{insn:08x} at 0x{pc:08x}")
+}
+
+/// Returns the word address of the byte address `addr`, failing if it is
+/// outside of the memory the circuit addresses.
+fn word_addr(addr: u32, cycle: u32) -> Result<u32> {
+    if addr >> 2 >= 1 << MEM_BITS {
+        return Err(ProverError::MemoryFault {
+            addr,
+            cycle: Some(cycle),
+        }
+        .into());
+    }
+    Ok(addr >> 2)
+}
+
+/// Returns the word address of an access of `size` bytes at `addr`, which must
+/// be aligned to its size.
+fn access_addr(addr: u32, size: u32, cycle: u32) -> Result<u32> {
+    if addr % size != 0 {
+        return Err(ProverError::UnalignedAccess {
+            addr,
+            cycle: Some(cycle),
+        }
+        .into());
+    }
+    word_addr(addr, cycle)
+}
+
+/// Returns the `size` bytes at the byte address `addr` of `word`.
+fn extract(word: u32, addr: u32, size: u32) -> u32 {
+    let mask = ((1u64 << (size * 8)) - 1) as u32;
+    (word >> ((addr % 4) * 8)) & mask
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use risc0_zkp::ZK_CYCLES;
+
+    use super::Emulator;
+    use crate::{elf::Program, host::ProverError, prove::exec::IoHandler};
+
+    struct NullIo;
+
+    impl IoHandler for NullIo {
+        fn on_commit(&mut self, _buf: &[u32]) {}
+
+        fn on_fault(&mut self, _msg: &str) {}
+
+        fn on_txrx(
+            &mut self,
+            channel: u32,
+            _cycle: u32,
+            _buf: &[u8],
+        ) -> Result<Vec<u8>, ProverError> {
+            Err(ProverError::UnknownChannel(channel))
+        }
+    }
+
+    fn program(code: &[u32]) -> Program {
+        let entry = 0x1000;
+        let image: BTreeMap<u32, u32> = code
+            .iter()
+            .enumerate()
+            .map(|(idx, insn)| (entry + 4 * idx as u32, *insn))
+            .collect();
+        Program { entry, image }
+    }
+
+    #[test]
+    fn instructions() {
+        let elf = program(&[
+            0xff900093, // addi x1, x0, -7
+            0x00300113, // addi x2, x0, 3
+            0x0220c1b3, // div x3, x1, x2
+            0x0220e233, // rem x4, x1, x2
+            0x022082b3, // mul x5, x1, x2
+            0x0220b333, // mulhu x6, x1, x2
+            0x40115393, // srai x7, x2, 1 (x7 = 1)
+            0x00002437, // lui x8, 2
+            0x00142023, // sw x1, 0(x8)
+            0x00240123, // sb x2, 2(x8)
+            0x00042483, // lw x9, 0(x8)
+            0x00244503, // lbu x10, 2(x8)
+            0x00000073, // ecall
+        ]);
+        let mut io = NullIo;
+        let mut emu = Emulator::new(&elf, &mut io);
+        emu.run().unwrap();
+        assert_eq!(emu.regs[3], -2i32 as u32);
+        assert_eq!(emu.regs[4], -1i32 as u32);
+        assert_eq!(emu.regs[5], -21i32 as u32);
+        assert_eq!(emu.regs[6], 2);
+        assert_eq!(emu.regs[7], 1);
+        assert_eq!(emu.regs[9], 0xff03fff9);
+        assert_eq!(emu.regs[10], 3);
+
+        // The image loads in cycles 1 to 13 and the body starts at 15. The
+        // ecall is decoded at 15 + 12 * 3 = 51, and the SHA phase that follows
+        // starts at 15 + 72 = 87 and halts right away, at 88.
+        assert_eq!(emu.halt_cycle, Some(88));
+        assert_eq!(emu.cycles(), (1 << emu.po2()) - ZK_CYCLES);
+        assert!(emu.cycles() > 89);
+    }
+
+    #[test]
+    fn invalid_instruction() {
+        let elf = program(&[0xffffffff]);
+        let mut io = NullIo;
+        let mut emu = Emulator::new(&elf, &mut io);
+        assert!(emu.run().is_err());
+    }
+}
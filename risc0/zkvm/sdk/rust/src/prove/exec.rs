@@ -12,13 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::{
-    cmp::Ordering,
-    ops::{Index, IndexMut},
-};
-use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use core::ops::{Index, IndexMut};
+use std::collections::BTreeMap;
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use lazy_regex::{regex, Captures};
 use log::{debug, trace};
 use risc0_zkp::core::sha::Sha;
@@ -26,7 +23,7 @@ use risc0_zkp::{
     adapter::{CircuitDef, CustomStep},
     core::{fp::Fp, log2_ceil, sha::DIGEST_WORDS},
     field::Elem,
-    prove::executor::Executor,
+    prove::executor::{Executor, ExecutorTrace},
     MAX_CYCLES_PO2, ZK_CYCLES,
 };
 use risc0_zkvm_circuit::CircuitImpl;
@@ -41,16 +38,21 @@ use risc0_zkvm_platform::{
     memory::INPUT,
     WORD_SIZE,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::{elf::Program, platform::memory::MEM_BITS, CODE_SIZE};
+use super::{
+    gdb::GdbServer,
+    trace::{self, TraceEvent, TraceSink},
+};
+use crate::{elf::Program, host::ProverError, platform::memory::MEM_BITS, CODE_SIZE};
 
 pub trait IoHandler {
     fn on_commit(&mut self, buf: &[u32]);
     fn on_fault(&mut self, msg: &str);
-    fn on_txrx(&mut self, channel: u32, buf: &[u8]) -> Vec<u8>;
+    fn on_txrx(&mut self, channel: u32, cycle: u32, buf: &[u8]) -> Result<Vec<u8>, ProverError>;
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
 struct MemoryEvent {
     pub cycle: u32,
     pub addr: u32,
@@ -69,9 +71,62 @@ impl std::fmt::Debug for MemoryEvent {
     }
 }
 
+/// The number of words in each page of [MemoryState].
+const PAGE_WORDS: usize = 1024;
+
+/// The number of pages needed to cover every word address the circuit can
+/// access.
+const NUM_PAGES: usize = (1 << MEM_BITS) / PAGE_WORDS;
+
+/// A page of guest memory, with a bit per word for whether it was loaded or
+/// written, and one for whether the guest has accessed it.
+struct Page {
+    words: [u32; PAGE_WORDS],
+    present: [u64; PAGE_WORDS / 64],
+    accessed: [u64; PAGE_WORDS / 64],
+}
+
+impl Page {
+    fn new() -> Box<Self> {
+        Box::new(Self {
+            words: [0; PAGE_WORDS],
+            present: [0; PAGE_WORDS / 64],
+            accessed: [0; PAGE_WORDS / 64],
+        })
+    }
+
+    fn get(&self, idx: usize) -> Option<u32> {
+        (self.present[idx / 64] & 1 << (idx % 64) != 0).then_some(self.words[idx])
+    }
+
+    fn set(&mut self, idx: usize, value: u32) {
+        self.words[idx] = value;
+        self.present[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn is_accessed(&self, idx: usize) -> bool {
+        self.accessed[idx / 64] & 1 << (idx % 64) != 0
+    }
+
+    fn access(&mut self, idx: usize) {
+        self.accessed[idx / 64] |= 1 << (idx % 64);
+    }
+}
+
+/// The memory of a guest, as pages of words that are allocated when first
+/// written, and the log of every access the circuit makes for `mem_check`.
 struct MemoryState {
-    pub memory: BTreeMap<u32, u32>,
-    pub history: BTreeSet<MemoryEvent>,
+    pages: Vec<Option<Box<Page>>>,
+    // Appended to as the circuit accesses memory, then sorted by address and
+    // cycle when mem_check starts reading it.
+    history: Vec<MemoryEvent>,
+    // The number of sorted events mem_check has read, and whether the events
+    // are sorted.
+    checked: usize,
+    sorted: bool,
+    // Whether accesses are logged at all, which they need not be when the
+    // execution is not proven.
+    logged: bool,
 }
 
 /// Align the given address `addr` upwards to alignment `align`.
@@ -82,127 +137,177 @@ const fn align_up(addr: usize, align: usize) -> usize {
 }
 
 impl MemoryState {
-    #[track_caller]
-    fn load_u8(&self, addr: u32) -> u8 {
+    fn new() -> Self {
+        Self::with_history(Vec::new())
+    }
+
+    fn with_history(history: Vec<MemoryEvent>) -> Self {
+        Self {
+            pages: (0..NUM_PAGES).map(|_| None).collect(),
+            history,
+            checked: 0,
+            sorted: false,
+            logged: true,
+        }
+    }
+
+    /// Returns the word at the word address `addr`, if it was loaded or
+    /// written.
+    fn get(&self, addr: u32) -> Option<u32> {
+        let addr = addr as usize;
+        self.pages
+            .get(addr / PAGE_WORDS)?
+            .as_ref()?
+            .get(addr % PAGE_WORDS)
+    }
+
+    /// Returns the page holding the word address `addr` and the index of the
+    /// word in it, allocating the page if needed.
+    fn page_mut(&mut self, addr: u32) -> Result<(&mut Page, usize), ProverError> {
+        let page =
+            self.pages
+                .get_mut(addr as usize / PAGE_WORDS)
+                .ok_or(ProverError::MemoryFault {
+                    addr: addr.wrapping_mul(WORD_SIZE as u32),
+                    cycle: None,
+                })?;
+        Ok((
+            page.get_or_insert_with(Page::new),
+            addr as usize % PAGE_WORDS,
+        ))
+    }
+
+    /// Returns the number of words that were loaded or written.
+    fn len(&self) -> usize {
+        self.pages
+            .iter()
+            .flatten()
+            .flat_map(|page| page.present.iter())
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+
+    fn push_event(&mut self, event: MemoryEvent) {
+        if self.logged {
+            self.history.push(event);
+            self.sorted = false;
+        }
+    }
+
+    /// Returns the next memory access for mem_check, in order of address and
+    /// cycle. Accesses to the same address in the same cycle are only checked
+    /// once, as the first of them.
+    fn next_event(&mut self) -> MemoryEvent {
+        if !self.sorted {
+            self.history.drain(..self.checked);
+            self.checked = 0;
+            self.history.sort_by_key(|event| (event.addr, event.cycle));
+            self.history
+                .dedup_by(|a, b| (a.addr, a.cycle) == (b.addr, b.cycle));
+            self.sorted = true;
+        }
+        let event = self
+            .history
+            .get(self.checked)
+            .expect("mem_check called on empty history")
+            .clone();
+        self.checked += 1;
+        event
+    }
+
+    fn load_u8(&self, addr: u32) -> Result<u8, ProverError> {
         // debug!("load_u8: 0x{addr:08X}");
         // align to the nearest word
         let aligned = addr & !(WORD_SIZE as u32 - 1);
         let offset = addr % WORD_SIZE as u32;
-        let word = self.load_u32(aligned);
-        ((word >> (offset * 8)) & 0xff) as u8
+        let word = self.load_u32(aligned)?;
+        Ok(((word >> (offset * 8)) & 0xff) as u8)
     }
 
-    #[track_caller]
-    fn load_u32(&self, addr: u32) -> u32 {
+    fn load_u32(&self, addr: u32) -> Result<u32, ProverError> {
         // debug!("load_u32: 0x{addr:08X}");
-        assert_eq!(addr % WORD_SIZE as u32, 0, "unaligned load");
-        let key = addr / 4;
-        match self.memory.get(&key) {
-            Some(word) => *word,
-            None => panic!("addr out of range: 0x{addr:08X}"),
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(ProverError::UnalignedAccess { addr, cycle: None });
         }
+        self.get(addr / 4)
+            .ok_or(ProverError::MemoryFault { addr, cycle: None })
     }
 
-    #[track_caller]
-    fn load_region_u32(&self, start: u32, size: u32) -> Vec<u32> {
+    fn load_region_u32(&self, start: u32, size: u32) -> Result<Vec<u32>, ProverError> {
         (start..start + size)
             .step_by(WORD_SIZE)
             .map(|addr| self.load_u32(addr))
             .collect()
     }
 
-    #[track_caller]
-    fn load_region(&self, addr: u32, size: u32) -> Vec<u8> {
-        let mut region = Vec::new();
-        for addr in addr..addr + size {
-            region.push(self.load_u8(addr));
+    fn load_region(&self, addr: u32, size: u32) -> Result<Vec<u8>, ProverError> {
+        let end = addr + size;
+        let mut region = Vec::with_capacity(size as usize);
+        let mut cur = addr;
+        while cur < end {
+            let aligned = cur & !(WORD_SIZE as u32 - 1);
+            let word = self.load_u32(aligned)?.to_le_bytes();
+            let stop = (end - aligned).min(WORD_SIZE as u32);
+            region.extend_from_slice(&word[(cur - aligned) as usize..stop as usize]);
+            cur = aligned + WORD_SIZE as u32;
         }
-        region
-    }
-
-    #[track_caller]
-    fn store_u8(&mut self, addr: u32, value: u8) {
-        // debug!("store_u8: 0x{addr:08X} <= 0x{value:08X}");
-        // align to the nearest word
-        let aligned = addr & !(WORD_SIZE as u32 - 1);
-        let offset = addr % WORD_SIZE as u32;
-        let key = aligned / 4;
-        let mut word = self.memory.get(&key).unwrap_or(&0) & !(0xff << (offset * 8));
-        word |= (value as u32) << (offset * 8);
-        self.store_u32(aligned, word);
+        Ok(region)
     }
 
-    #[track_caller]
-    fn store_u32(&mut self, addr: u32, value: u32) {
+    fn store_u32(&mut self, addr: u32, value: u32) -> Result<(), ProverError> {
         // debug!("store_u32: 0x{addr:08X} <= 0x{value:08X}");
-        assert_eq!(addr % WORD_SIZE as u32, 0, "unaligned store");
-        let key = addr / 4;
-        match self.memory.entry(key) {
-            Entry::Occupied(mut entry) => {
-                let min = MemoryEvent {
-                    cycle: 0,
-                    addr: key,
-                    data: 0,
-                    is_write: false,
-                };
-                let max = MemoryEvent {
-                    cycle: 0,
-                    addr: key + 1,
-                    data: 0,
-                    is_write: false,
-                };
-                if let Some(txn) = self.history.range(min..max).next() {
-                    let last_value = *entry.get();
-                    if txn.addr == key && last_value != value {
-                        debug!("addr: 0x{key:08X}, value: 0x{value:08X}, txn: {txn:?}");
-                        // The guest has actually touched this memory, and we are not writing the
-                        // same value
-                        panic!("Host cannot mutate existing memory");
-                    }
-                }
-                entry.insert(value);
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(value);
+        if addr % WORD_SIZE as u32 != 0 {
+            return Err(ProverError::UnalignedAccess { addr, cycle: None });
+        }
+        let (page, idx) = self.page_mut(addr / 4)?;
+        if let Some(last_value) = page.get(idx) {
+            if page.is_accessed(idx) && last_value != value {
+                debug!("addr: 0x{addr:08X}, value: 0x{value:08X}, last: 0x{last_value:08X}");
+                // The guest has actually touched this memory, and we are not writing the
+                // same value
+                return Err(ProverError::HostMemoryConflict { addr });
             }
         }
+        page.set(idx, value);
+        Ok(())
     }
 
-    #[track_caller]
-    fn store_region(&mut self, addr: u32, slice: &[u8]) {
+    fn store_region(&mut self, addr: u32, slice: &[u8]) -> Result<(), ProverError> {
         // debug!("store_region: 0x{addr:08X} <= {} bytes", slice.len());
-        for i in 0..slice.len() {
-            self.store_u8(addr + i as u32, slice[i]);
+        let mut cur = addr;
+        let mut rest = slice;
+        while !rest.is_empty() {
+            let aligned = cur & !(WORD_SIZE as u32 - 1);
+            let offset = (cur - aligned) as usize;
+            let len = rest.len().min(WORD_SIZE - offset);
+            let mut word = self.get(aligned / 4).unwrap_or(0).to_le_bytes();
+            word[offset..offset + len].copy_from_slice(&rest[..len]);
+            self.store_u32(aligned, u32::from_le_bytes(word))?;
+            rest = &rest[len..];
+            cur = aligned + WORD_SIZE as u32;
         }
+        Ok(())
     }
 
-    #[track_caller]
-    fn store_region_u32(&mut self, addr: u32, slice: &[u32]) {
-        assert!(addr % WORD_SIZE as u32 == 0);
+    fn store_region_u32(&mut self, addr: u32, slice: &[u32]) -> Result<(), ProverError> {
         for (offset, word) in slice.iter().enumerate() {
-            self.store_u32(addr + WORD_SIZE as u32 * offset as u32, *word);
+            self.store_u32(addr + WORD_SIZE as u32 * offset as u32, *word)?;
         }
+        Ok(())
     }
 
-    fn strlen(&self, addr: u32) -> usize {
-        let mut addr = addr;
+    fn strlen(&self, addr: u32) -> Result<usize, ProverError> {
         let mut len = 0;
-        while self.load_u8(addr) != 0 {
-            addr += 1;
-            len += 1;
+        loop {
+            let cur = addr + len as u32;
+            let aligned = cur & !(WORD_SIZE as u32 - 1);
+            let offset = (cur - aligned) as usize;
+            let word = self.load_u32(aligned)?.to_le_bytes();
+            match word[offset..].iter().position(|byte| *byte == 0) {
+                Some(pos) => return Ok(len + pos),
+                None => len += WORD_SIZE - offset,
+            }
         }
-        len
-    }
-
-    fn pop_history(&mut self) -> MemoryEvent {
-        let event = self
-            .history
-            .iter()
-            .next()
-            .expect("mem_check called on empty history")
-            .clone();
-        self.history.remove(&event);
-        event
     }
 }
 
@@ -210,22 +315,10 @@ pub struct MachineContext<'a, H: IoHandler> {
     memory: MemoryState,
     io: &'a mut H,
     cur_host_to_guest_offset: usize,
-}
-
-impl PartialOrd for MemoryEvent {
-    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
-        Some(self.cmp(rhs))
-    }
-}
-
-impl Ord for MemoryEvent {
-    fn cmp(&self, rhs: &Self) -> Ordering {
-        match self.addr.cmp(&rhs.addr) {
-            std::cmp::Ordering::Equal => {}
-            ord => return ord,
-        }
-        self.cycle.cmp(&rhs.cycle)
-    }
+    trace: Option<&'a mut dyn TraceSink>,
+    debugger: Option<&'a mut GdbServer>,
+    // The registers x0 to x31, only tracked for the debugger.
+    regs: [u32; 32],
 }
 
 impl<'a, H: IoHandler> CircuitDef<MachineContext<'a, H>> for CircuitImpl {}
@@ -238,7 +331,7 @@ impl<'a, H: IoHandler> CustomStep for MachineContext<'a, H> {
                 Ok(vec![x0, x1, x2, x3])
             }
             "log" => {
-                self.log(extra, args);
+                self.log(extra, args)?;
                 Ok(vec![])
             }
             "memCheck" => {
@@ -258,17 +351,6 @@ impl<'a, H: IoHandler> CustomStep for MachineContext<'a, H> {
     }
 }
 
-impl MemoryState {
-    fn new() -> Self {
-        Self {
-            memory: BTreeMap::new(),
-            history: BTreeSet::new(),
-        }
-    }
-}
-
-impl MemoryState {}
-
 fn split_word(value: u32) -> (Fp, Fp) {
     (Fp::new(value & 0xffff), Fp::new(value >> 16))
 }
@@ -285,9 +367,19 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
             memory: MemoryState::new(),
             io,
             cur_host_to_guest_offset: INPUT.start(),
+            trace: None,
+            debugger: None,
+            regs: [0; 32],
         }
     }
 
+    /// Stops logging memory accesses, for executions that are not proven and
+    /// so never check memory.
+    pub(crate) fn without_history(mut self) -> Self {
+        self.memory.logged = false;
+        self
+    }
+
     fn divide32(&self, numer: (Fp, Fp), denom: (Fp, Fp)) -> ((Fp, Fp), (Fp, Fp)) {
         let numer = merge_word(numer);
         let denom = merge_word(denom);
@@ -299,10 +391,20 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
         (split_word(quot), split_word(rem))
     }
 
-    fn log(&self, msg: &str, args: &[Fp]) {
+    fn log(&mut self, msg: &str, args: &[Fp]) -> Result<()> {
+        if let Some(kind) = trace::decoded_mnemonic(msg) {
+            self.on_decode(args[0].into(), args[1].into(), kind)?;
+        } else if self.debugger.is_some() && msg.contains(" Final: ") {
+            self.on_final(
+                args[1].into(),
+                merge_word((args[3], args[2])),
+                args[4].into(),
+            );
+        }
+
         if log::max_level() < log::LevelFilter::Trace {
             // Don't bother to format it if we're not even logging.
-            return;
+            return Ok(());
         }
 
         // "msg" is given to us in C++-style formatting, so interpret it.
@@ -329,10 +431,40 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
             args_left
         );
         trace!("{}", formatted);
+        Ok(())
+    }
+
+    /// Reports the instruction at `pc`, which a cycle just decoded, to the
+    /// trace sink and the debugger.
+    fn on_decode(&mut self, cycle: u32, pc: u32, kind: &'static str) -> Result<()> {
+        if let Some(sink) = &mut self.trace {
+            sink.on_event(&TraceEvent::Instruction {
+                cycle,
+                pc,
+                insn: self.memory.get(pc / 4).unwrap_or(0),
+                kind,
+            });
+        }
+        if let Some(debugger) = &mut self.debugger {
+            let memory = &self.memory;
+            debugger.on_instruction(pc, &self.regs, &|addr| memory.load_u8(addr).ok())?;
+        }
+        Ok(())
+    }
+
+    /// Tracks the registers for the debugger, given the result that a cycle
+    /// just finished the instruction at `pc` with.
+    fn on_final(&mut self, pc: u32, result: u32, rd: u32) {
+        let insn = self.memory.get(pc / 4).unwrap_or(0);
+        // Stores, branches and system calls leave rd alone.
+        let writes_rd = !matches!((insn >> 2) & 0x1f, 0b01000 | 0b11000 | 0b11100);
+        if writes_rd && rd != 0 {
+            self.regs[rd as usize] = result;
+        }
     }
 
     fn mem_check(&mut self) -> (Fp, Fp, Fp, Fp, Fp) {
-        let event = self.memory.pop_history();
+        let event = self.memory.next_event();
         let parts = split_word(event.data);
         // debug!("mem_check: {event:?}");
         (
@@ -345,53 +477,90 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
     }
 
     fn mem_read(&mut self, cycle: Fp, addr: Fp) -> (Fp, Fp) {
-        let cycle: u32 = cycle.into();
-        let addr: u32 = addr.into();
+        split_word(self.read_word(cycle.into(), addr.into()))
+    }
+
+    /// Reads the word at the word address `addr` in `cycle`, recording the
+    /// access like the circuit's reads.
+    pub(crate) fn read_word(&mut self, cycle: u32, addr: u32) -> u32 {
         // debug!("[{}] R: 0x{:08X}", cycle, addr);
-        let data = *self.memory.memory.entry(addr).or_insert(0);
+        let data = match self.memory.page_mut(addr) {
+            Ok((page, idx)) => {
+                let data = page.get(idx).unwrap_or(0);
+                page.set(idx, data);
+                page.access(idx);
+                data
+            }
+            // The circuit cannot address memory beyond MEM_BITS.
+            Err(_) => 0,
+        };
         // debug!("data: 0x{data:08X}");
-        self.memory.history.insert(MemoryEvent {
+        self.memory.push_event(MemoryEvent {
             cycle,
             addr,
             data,
             is_write: false,
         });
-        split_word(data)
+        if let Some(sink) = &mut self.trace {
+            sink.on_event(&TraceEvent::MemoryRead {
+                cycle,
+                addr: addr * 4,
+                value: data,
+            });
+        }
+        data
     }
 
     fn mem_write(&mut self, cycle: Fp, addr: Fp, value: (Fp, Fp)) -> Result<()> {
-        let cycle: u32 = cycle.into();
-        let addr: u32 = addr.into();
-        let data = merge_word(value);
+        self.write_word(cycle.into(), addr.into(), merge_word(value))
+    }
+
+    /// Writes `data` to the word address `addr` in `cycle`, recording the
+    /// access like the circuit's writes, and handles writes to the GPIO
+    /// addresses.
+    pub(crate) fn write_word(&mut self, cycle: u32, addr: u32, data: u32) -> Result<()> {
         let is_write = addr < (1 << (MEM_BITS - 1));
         // debug!("[{}] W: 0x{:08X} <= 0x{:08X}", cycle, addr, data);
-        self.memory.history.insert(MemoryEvent {
+        self.memory.push_event(MemoryEvent {
             cycle,
             addr,
             data,
             is_write,
         });
-        match self.memory.memory.entry(addr) {
-            Entry::Occupied(mut entry) => {
-                if *entry.get() != data && !is_write {
-                    bail!(
-                        "Double wrote write-once memory at 0x{:08X}. old: 0x{:08X}, new: 0x{:08X}",
-                        addr * 4,
-                        *entry.get(),
-                        data
-                    );
+        if let Some(sink) = &mut self.trace {
+            sink.on_event(&TraceEvent::MemoryWrite {
+                cycle,
+                addr: addr * 4,
+                value: data,
+            });
+        }
+        let (page, idx) = self
+            .memory
+            .page_mut(addr)
+            .map_err(|err| err.at_cycle(cycle))?;
+        if let Some(old) = page.get(idx) {
+            if old != data && !is_write {
+                debug!("old: 0x{old:08X}, new: 0x{data:08X}");
+                return Err(ProverError::DoubleWrite {
+                    addr: addr * 4,
+                    cycle: Some(cycle),
                 }
-                *entry.get_mut() = data;
-            }
-            Entry::Vacant(entry) => {
-                entry.insert(data);
+                .into());
             }
-        };
-        self.on_write(cycle, addr * 4, data);
-        Ok(())
+        }
+        page.set(idx, data);
+        page.access(idx);
+        self.on_write(cycle, addr * 4, data)
+            .map_err(|err| err.at_cycle(cycle).into())
+    }
+
+    /// Returns the number of bytes of memory the guest has touched, including
+    /// its loaded image.
+    pub fn touched_bytes(&self) -> usize {
+        self.memory.len() * WORD_SIZE
     }
 
-    fn on_write(&mut self, cycle: u32, addr: u32, value: u32) {
+    fn on_write(&mut self, cycle: u32, addr: u32, value: u32) -> Result<(), ProverError> {
         use risc0_zkvm_platform::io::addr::GPIO_LOG;
 
         // debug!("on_write: 0x{:08X}: 0x{:08X}", addr, value);
@@ -401,7 +570,7 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
                 const SZ: usize = core::mem::size_of::<IoDescriptor>();
                 let descbuf: [u32; SZ / WORD_SIZE] = self
                     .memory
-                    .load_region_u32(value, SZ as u32)
+                    .load_region_u32(value, SZ as u32)?
                     .as_slice()
                     .try_into()
                     .unwrap();
@@ -413,16 +582,18 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
                     desc.addr, desc.size
                 );
 
-                let buf = self.memory.load_region_u32(desc.addr, desc.size);
+                let buf = self.memory.load_region_u32(desc.addr, desc.size)?;
                 debug!("Data: {:08X?}", &buf);
                 self.io.on_commit(buf.as_slice());
             }
             GPIO_FAULT => {
                 debug!("on_write> GPIO_FAULT");
-                let len = self.memory.strlen(value);
-                let buf = self.memory.load_region(value, len as u32);
-                let str = String::from_utf8(buf).unwrap();
+                let len = self.memory.strlen(value)?;
+                let buf = self.memory.load_region(value, len as u32)?;
+                let str = String::from_utf8_lossy(&buf).into_owned();
                 self.io.on_fault(&str);
+                // A compliant host stops running a guest that faults.
+                return Err(ProverError::GuestPanic(str));
             }
             GPIO_GETKEY => {
                 debug!("on_write> GPIO_GETKEY");
@@ -430,27 +601,26 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
             }
             GPIO_LOG => {
                 debug!("on_write> GPIO_LOG");
-                let len = self.memory.strlen(value);
-                let buf = self.memory.load_region(value, len as u32);
-                let str = String::from_utf8(buf).unwrap();
+                let len = self.memory.strlen(value)?;
+                let buf = self.memory.load_region(value, len as u32)?;
+                let str = String::from_utf8_lossy(&buf);
                 debug!("R0VM[C{cycle}> {}", str);
             }
             GPIO_SENDRECV_ADDR => {
                 debug!("on_write> GPIO_SENDRECV_ADDR");
-                let channel = self.memory.load_u32(GPIO_SENDRECV_CHANNEL);
-                let size = self.memory.load_u32(GPIO_SENDRECV_SIZE);
-                let region = self.memory.load_region(value, size);
-                let result = self.io.on_txrx(channel, &region);
+                let channel = self.memory.load_u32(GPIO_SENDRECV_CHANNEL)?;
+                let size = self.memory.load_u32(GPIO_SENDRECV_SIZE)?;
+                let region = self.memory.load_region(value, size)?;
+                let result = self.io.on_txrx(channel, cycle, &region)?;
                 let aligned_len = align_up(result.len(), WORD_SIZE);
-                assert!(
-                    self.cur_host_to_guest_offset + WORD_SIZE + aligned_len < INPUT.end(),
-                    "Read buffer overrun"
-                );
+                if self.cur_host_to_guest_offset + WORD_SIZE + aligned_len >= INPUT.end() {
+                    return Err(ProverError::InputOverrun);
+                }
                 self.memory
-                    .store_u32(self.cur_host_to_guest_offset as u32, result.len() as u32);
+                    .store_u32(self.cur_host_to_guest_offset as u32, result.len() as u32)?;
                 self.cur_host_to_guest_offset += WORD_SIZE;
                 self.memory
-                    .store_region(self.cur_host_to_guest_offset as u32, &result);
+                    .store_region(self.cur_host_to_guest_offset as u32, &result)?;
                 self.cur_host_to_guest_offset += aligned_len;
             }
             GPIO_SHA => {
@@ -458,19 +628,20 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
                 const SZ: usize = core::mem::size_of::<SHADescriptor>();
                 let descbuf: [u32; SZ / WORD_SIZE] = self
                     .memory
-                    .load_region_u32(value, SZ as u32)
+                    .load_region_u32(value, SZ as u32)?
                     .try_into()
                     .unwrap();
                 // SAFETY: SHADescriptor is a plain-old-data type with
                 // repr(C) and no pointers so it's safe to fill it from bytes.
                 let desc: SHADescriptor = unsafe { std::mem::transmute(descbuf) };
-                self.process_sha(&desc);
+                self.process_sha(&desc)?;
             }
             _ => {}
         };
+        Ok(())
     }
 
-    fn process_sha(&mut self, desc: &SHADescriptor) {
+    fn process_sha(&mut self, desc: &SHADescriptor) -> Result<(), ProverError> {
         let sha_type: u16 = ((desc.type_count & 0xFFFF) >> 4) as u16;
         let count: u16 = (desc.type_count & 0xFFFF) as u16;
         debug!(
@@ -481,13 +652,13 @@ impl<'a, H: IoHandler> MachineContext<'a, H> {
         let sha = risc0_zkp::core::sha::default_implementation();
         let words = self
             .memory
-            .load_region_u32(desc.source as u32, (count * 64) as u32);
+            .load_region_u32(desc.source as u32, (count * 64) as u32)?;
         let digest = sha.hash_raw_words(bytemuck::cast_slice(words.as_slice()));
 
         debug!("Digest result is {:X?}", digest.as_slice());
 
         self.memory
-            .store_region_u32(desc.digest as u32, digest.as_slice());
+            .store_region_u32(desc.digest as u32, digest.as_slice())
     }
 }
 
@@ -529,6 +700,14 @@ enum CodeIndex {
     Data2High,
 }
 
+/// The number of cycles each instruction takes, counting from the first
+/// cycle of the body.
+pub(crate) const INSN_CYCLES: usize = 3;
+
+/// The number of cycles in each phase of the SHA accelerator, counting from
+/// the first cycle of the body.
+pub(crate) const SHA_CYCLES: usize = 72;
+
 const ZERO: Fp = Fp::new(0);
 const ONE: Fp = Fp::new(1);
 
@@ -618,14 +797,14 @@ where
         loop {
             self.start();
 
-            let inst_phase = (self.cycle - base_cycle) % 3;
+            let inst_phase = (self.cycle - base_cycle) % INSN_CYCLES;
             if inst_phase == 2 {
                 self.code[CodeIndex::TypeFinal] = ONE;
             } else {
                 self.code[CodeIndex::TypeNormal] = ONE;
             }
 
-            let sha_phase = (self.cycle - base_cycle) % 72;
+            let sha_phase = (self.cycle - base_cycle) % SHA_CYCLES;
             if sha_phase < 4 {
                 let init1 = split_word(SHA_INIT[3 - sha_phase]);
                 let init2 = split_word(SHA_INIT[7 - sha_phase]);
@@ -679,6 +858,17 @@ where
     }
 }
 
+/// Returns the row that [load_code] writes to finish the program when a trace
+/// runs out of room. `cycle` is the loader's cycle counter at that point, which
+/// is one past the row being written since the body row that found no room is
+/// counted as well.
+pub fn fini_code(cycle: usize) -> [Fp; CODE_SIZE] {
+    let mut code = CodeRegisters::new();
+    code[CodeIndex::Cycle] = Fp::new(cycle as u32);
+    code[CodeIndex::TypeFini] = ONE;
+    code.0
+}
+
 pub fn load_code<F>(start_addr: u32, image: &BTreeMap<u32, u32>, step: F) -> Result<()>
 where
     F: FnMut(&[Fp], usize) -> Result<bool>,
@@ -694,6 +884,56 @@ where
     Ok(())
 }
 
+/// The trace of a guest that ran to completion with
+/// [RV32Executor::execute_with_monitor], before it is finalized for proving.
+/// The memory history is kept so that the finalizing verify cycles, and the
+/// zero-knowledge randomness, are only produced when the trace is proven.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SavedTrace {
+    pub po2: u32,
+    pub cycle: u32,
+    code: Vec<u32>,
+    data: Vec<u32>,
+    output: Vec<u32>,
+    history: Vec<MemoryEvent>,
+}
+
+/// Restores an executor from a [SavedTrace] and finalizes it for proving,
+/// seeding its zero-knowledge randomness with `seed` if given.
+pub fn load_trace<'a, H: IoHandler>(
+    circuit: &'static CircuitImpl,
+    io: &'a mut H,
+    trace: &SavedTrace,
+    seed: Option<u64>,
+) -> Result<Executor<CircuitImpl, MachineContext<'a, H>>> {
+    let to_fps = |words: &[u32]| words.iter().map(|word| Fp::new(*word)).collect();
+    let mut machine = MachineContext::new(io);
+    machine.memory = MemoryState::with_history(trace.history.clone());
+    let mut executor = Executor::from_trace(
+        circuit,
+        machine,
+        ExecutorTrace {
+            po2: trace.po2 as usize,
+            cycle: trace.cycle as usize,
+            code: to_fps(&trace.code),
+            data: to_fps(&trace.data),
+            output: to_fps(&trace.output),
+        },
+    )?;
+    executor.seed = seed;
+    executor.finalize();
+    Ok(executor)
+}
+
+/// The number of cycles between calls to the monitor of
+/// [RV32Executor::run_with_monitor].
+pub const MONITOR_INTERVAL: usize = 1 << 16;
+
+/// Returns the log2 of the smallest trace that fits the loading of `elf`.
+pub(crate) fn min_po2(elf: &Program) -> usize {
+    log2_ceil(elf.image.len() + 3 + ZK_CYCLES)
+}
+
 pub struct RV32Executor<'a, H: IoHandler> {
     elf: &'a Program,
     pub executor: Executor<CircuitImpl, MachineContext<'a, H>>,
@@ -703,24 +943,158 @@ impl<'a, H: IoHandler> RV32Executor<'a, H> {
     pub fn new(circuit: &'static CircuitImpl, elf: &'a Program, io: &'a mut H) -> Self {
         debug!("image.size(): {}", elf.image.len());
         let machine = MachineContext::new(io);
-        let min_po2 = log2_ceil(elf.image.len() + 3 + ZK_CYCLES);
-        let executor = Executor::new(circuit, machine, min_po2, MAX_CYCLES_PO2);
+        let executor = Executor::new(circuit, machine, min_po2(elf), MAX_CYCLES_PO2);
         Self { elf, executor }
     }
 
+    /// Reports each instruction the guest runs, and each memory access, to
+    /// `sink`.
+    pub fn with_trace_sink(mut self, sink: &'a mut dyn TraceSink) -> Self {
+        self.executor.custom.trace = Some(sink);
+        self
+    }
+
+    /// Stops the guest before its first instruction, and lets GDB debug it
+    /// through `server`.
+    pub fn with_gdb_server(mut self, server: &'a mut GdbServer) -> Self {
+        self.executor.custom.debugger = Some(server);
+        self
+    }
+
     pub fn run(&mut self) -> Result<()> {
-        load_code(self.elf.entry, &self.elf.image, |chunk, fini| {
-            self.executor.step(chunk, fini)
-        })?;
+        self.run_with_monitor(|_| Ok(()))
+    }
+
+    /// Like [RV32Executor::run], but calls `monitor` with the current cycle
+    /// every [MONITOR_INTERVAL] cycles, stopping with its error if it fails.
+    pub fn run_with_monitor<F>(&mut self, monitor: F) -> Result<()>
+    where
+        F: FnMut(usize) -> Result<()>,
+    {
+        self.execute_with_monitor(monitor)?;
         self.executor.finalize();
         Ok(())
     }
 
+    /// Saves the trace of a guest that ran to completion with
+    /// [RV32Executor::execute_with_monitor].
+    pub fn save_trace(&self) -> SavedTrace {
+        let to_words = |fps: &[Fp]| fps.iter().map(|fp| u32::from(*fp)).collect();
+        let trace = self.executor.to_trace();
+        SavedTrace {
+            po2: trace.po2 as u32,
+            cycle: trace.cycle as u32,
+            code: to_words(&trace.code),
+            data: to_words(&trace.data),
+            output: to_words(&trace.output),
+            history: self.executor.custom.memory.history.to_vec(),
+        }
+    }
+
+    /// Runs the guest to completion like [RV32Executor::run_with_monitor],
+    /// without finalizing the trace for proving.
+    pub fn execute_with_monitor<F>(&mut self, mut monitor: F) -> Result<()>
+    where
+        F: FnMut(usize) -> Result<()>,
+    {
+        let result = load_code(self.elf.entry, &self.elf.image, |chunk, fini| {
+            let cycle = self.executor.cycle;
+            if cycle % MONITOR_INTERVAL == 0 {
+                monitor(cycle)?;
+            }
+            step(&mut self.executor, chunk, fini)
+        });
+        self.on_exit(result)
+    }
+
     pub fn run_without_seal(&mut self) -> Result<()> {
-        load_code(self.elf.entry, &self.elf.image, |chunk, fini| {
-            self.executor.step(chunk, fini)
-        })?;
+        let result = load_code(self.elf.entry, &self.elf.image, |chunk, fini| {
+            step(&mut self.executor, chunk, fini)
+        });
+        self.on_exit(result)?;
         self.executor.finalize();
         Ok(())
     }
+
+    /// Tells the debugger, if any, how the guest exited.
+    fn on_exit(&mut self, result: Result<()>) -> Result<()> {
+        if let Some(debugger) = &mut self.executor.custom.debugger {
+            if let Err(err) = debugger.on_exit(result.is_ok()) {
+                debug!("GDB did not hear that the guest exited: {err}");
+            }
+        }
+        result
+    }
+}
+
+/// Runs one cycle of the executor, failing with
+/// [ProverError::CycleLimitExceeded] rather than expanding the trace beyond
+/// [MAX_CYCLES_PO2].
+fn step<H: IoHandler>(
+    executor: &mut Executor<CircuitImpl, MachineContext<H>>,
+    code: &[Fp],
+    fini: usize,
+) -> Result<bool> {
+    if !executor.halted && executor.cycle + fini + ZK_CYCLES >= 1 << MAX_CYCLES_PO2 {
+        return Err(ProverError::CycleLimitExceeded.into());
+    }
+    executor.step(code, fini)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryEvent, MemoryState};
+    use crate::host::ProverError;
+
+    fn event(cycle: u32, addr: u32, data: u32) -> MemoryEvent {
+        MemoryEvent {
+            cycle,
+            addr,
+            data,
+            is_write: false,
+        }
+    }
+
+    #[test]
+    fn regions() {
+        let mut memory = MemoryState::new();
+        memory.store_region(0x1001, b"hello world\0").unwrap();
+        assert_eq!(memory.load_region(0x1001, 11).unwrap(), b"hello world");
+        assert_eq!(memory.load_region(0x1003, 3).unwrap(), b"llo");
+        assert_eq!(memory.strlen(0x1001).unwrap(), 11);
+        assert_eq!(memory.load_u32(0x1000).unwrap(), 0x6c656800);
+        assert_eq!(memory.len(), 4);
+        assert_eq!(
+            memory.load_u32(0x2000),
+            Err(ProverError::MemoryFault {
+                addr: 0x2000,
+                cycle: None
+            })
+        );
+    }
+
+    #[test]
+    fn host_memory_conflict() {
+        let mut memory = MemoryState::new();
+        memory.store_u32(0x1000, 1).unwrap();
+        memory.store_u32(0x1000, 2).unwrap();
+        let (page, idx) = memory.page_mut(0x1000 / 4).unwrap();
+        page.access(idx);
+        memory.store_u32(0x1000, 2).unwrap();
+        assert_eq!(
+            memory.store_u32(0x1000, 3),
+            Err(ProverError::HostMemoryConflict { addr: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn history() {
+        let mut memory = MemoryState::new();
+        memory.push_event(event(5, 2, 0));
+        memory.push_event(event(3, 2, 1));
+        memory.push_event(event(3, 2, 2));
+        memory.push_event(event(9, 1, 3));
+        let events: Vec<_> = (0..3).map(|_| memory.next_event()).collect();
+        assert_eq!(events, [event(9, 1, 3), event(3, 2, 1), event(5, 2, 0)]);
+    }
 }
@@ -0,0 +1,375 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+    iter,
+    net::{Ipv4Addr, TcpListener},
+};
+
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+
+/// Tells GDB that the guest is a 32-bit RISC-V machine with the default
+/// registers: x0 to x31, then pc.
+const TARGET_XML: &str = "<?xml version=\"1.0\"?>\
+    <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+    <target version=\"1.0\"><architecture>riscv:rv32</architecture></target>";
+
+/// The reply to GDB when the guest stops, which is always reported as a
+/// SIGTRAP.
+const STOP_REPLY: &str = "S05";
+
+/// The most bytes of memory sent in one reply, which keeps the reply within
+/// the packet size announced to GDB.
+const MAX_MEMORY_READ: usize = 0x800;
+
+/// A server for the GDB remote serial protocol, which lets GDB debug a guest
+/// run by the Rust prover, see
+/// [ProverOpts::with_gdb_server](crate::host::ProverOpts::with_gdb_server).
+///
+/// The guest stops before its first instruction. GDB can then read the
+/// registers and memory, set software breakpoints, single step and continue.
+/// Writing registers or memory is not supported, and neither is interrupting
+/// a running guest: set a breakpoint instead.
+pub struct GdbServer {
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    no_ack: bool,
+    breakpoints: BTreeSet<u32>,
+    state: State,
+    // Whether GDB resumed the guest and waits to hear that it stopped.
+    waiting: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Stepping,
+    Running,
+    Detached,
+}
+
+enum Action {
+    Reply(String),
+    Resume(State),
+    Detach,
+    Kill,
+}
+
+impl GdbServer {
+    /// Serves GDB over the given connection, such as the two ends of a pipe.
+    pub fn new(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            no_ack: false,
+            breakpoints: BTreeSet::new(),
+            state: State::Stepping,
+            waiting: false,
+        }
+    }
+
+    /// Waits for GDB to connect to the given TCP port on the loopback
+    /// interface, as with `target remote localhost:<port>`.
+    pub fn listen(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let (stream, addr) = listener.accept()?;
+        debug!("GDB connected from {addr}");
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream.try_clone()?, stream))
+    }
+
+    /// Called before the guest runs the instruction at `pc`, with the
+    /// registers x0 to x31 and a reader for the bytes of memory. Serves GDB
+    /// until it resumes the guest if the guest should stop here, and fails if
+    /// GDB kills the guest.
+    pub(crate) fn on_instruction(
+        &mut self,
+        pc: u32,
+        regs: &[u32; 32],
+        memory: &dyn Fn(u32) -> Option<u8>,
+    ) -> Result<()> {
+        let stop = match self.state {
+            State::Stepping => true,
+            State::Running => self.breakpoints.contains(&pc),
+            State::Detached => false,
+        };
+        if !stop {
+            return Ok(());
+        }
+        if self.waiting {
+            self.send(STOP_REPLY)?;
+            self.waiting = false;
+        }
+        loop {
+            let packet = self.recv()?;
+            match self.handle(&packet, pc, regs, memory) {
+                Action::Reply(reply) => self.send(&reply)?,
+                Action::Resume(state) => {
+                    self.state = state;
+                    self.waiting = true;
+                    return Ok(());
+                }
+                Action::Detach => {
+                    self.send("OK")?;
+                    self.breakpoints.clear();
+                    self.state = State::Detached;
+                    return Ok(());
+                }
+                Action::Kill => bail!("The guest was killed by GDB"),
+            }
+        }
+    }
+
+    /// Tells GDB that the guest exited, successfully or not, if it waits for
+    /// the guest to stop.
+    pub(crate) fn on_exit(&mut self, success: bool) -> Result<()> {
+        if !self.waiting {
+            return Ok(());
+        }
+        self.waiting = false;
+        // A failed guest is reported as killed by SIGABRT.
+        self.send(if success { "W00" } else { "X06" })
+    }
+
+    fn handle(
+        &mut self,
+        packet: &str,
+        pc: u32,
+        regs: &[u32; 32],
+        memory: &dyn Fn(u32) -> Option<u8>,
+    ) -> Action {
+        let reply = |reply: &str| Action::Reply(reply.to_string());
+        let mut chars = packet.chars();
+        let cmd = chars.next();
+        let args = chars.as_str();
+        match cmd {
+            Some('?') => reply(STOP_REPLY),
+            Some('g') => Action::Reply(regs.iter().chain(iter::once(&pc)).map(hex_word).collect()),
+            Some('p') => match u32::from_str_radix(args, 16) {
+                Ok(idx) if idx < 32 => Action::Reply(hex_word(&regs[idx as usize])),
+                Ok(32) => Action::Reply(hex_word(&pc)),
+                _ => reply("E00"),
+            },
+            Some('m') => match parse_pair(args) {
+                Some((addr, len)) => {
+                    let bytes: Vec<u8> = (0..len.min(MAX_MEMORY_READ as u32))
+                        .map_while(|offset| memory(addr.wrapping_add(offset)))
+                        .collect();
+                    if bytes.is_empty() && len > 0 {
+                        reply("E14")
+                    } else {
+                        Action::Reply(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+                    }
+                }
+                None => reply("E00"),
+            },
+            // Only software breakpoints are supported, which GDB falls back on
+            // after an empty reply to the other kinds.
+            Some(op @ ('Z' | 'z')) => match args.strip_prefix("0,").and_then(parse_pair) {
+                Some((addr, _)) => {
+                    if op == 'Z' {
+                        self.breakpoints.insert(addr);
+                    } else {
+                        self.breakpoints.remove(&addr);
+                    }
+                    reply("OK")
+                }
+                None => reply(""),
+            },
+            Some('c') => Action::Resume(State::Running),
+            Some('s') => Action::Resume(State::Stepping),
+            Some('D') => Action::Detach,
+            Some('k') => Action::Kill,
+            Some('H') => reply("OK"),
+            Some('q') => self.query(args),
+            Some('Q') if args == "StartNoAckMode" => {
+                self.no_ack = true;
+                reply("OK")
+            }
+            _ => reply(""),
+        }
+    }
+
+    fn query(&self, query: &str) -> Action {
+        let reply = |reply: &str| Action::Reply(reply.to_string());
+        if query.starts_with("Supported") {
+            reply("PacketSize=1000;qXfer:features:read+;QStartNoAckMode+")
+        } else if query == "Attached" {
+            reply("1")
+        } else if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            match parse_pair(range) {
+                Some((offset, len)) => {
+                    let start = (offset as usize).min(TARGET_XML.len());
+                    let end = start.saturating_add(len as usize).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+                    Action::Reply(format!("{more}{}", &TARGET_XML[start..end]))
+                }
+                None => reply("E00"),
+            }
+        } else {
+            reply("")
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8> {
+        let mut byte = [0];
+        self.reader
+            .read_exact(&mut byte)
+            .map_err(|err| anyhow!("GDB disconnected: {err}"))?;
+        Ok(byte[0])
+    }
+
+    /// Receives the next packet from GDB, skipping the acknowledgements of
+    /// the packets sent to it, and any interrupt requests.
+    fn recv(&mut self) -> Result<String> {
+        loop {
+            while self.read_byte()? != b'$' {}
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let digits = [self.read_byte()?, self.read_byte()?];
+            let expected = std::str::from_utf8(&digits)
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            if expected != Some(checksum(&data)) {
+                self.writer.write_all(b"-")?;
+                self.writer.flush()?;
+                continue;
+            }
+            if !self.no_ack {
+                self.writer.write_all(b"+")?;
+            }
+            let packet = String::from_utf8(data)?;
+            debug!("GDB> {packet}");
+            return Ok(packet);
+        }
+    }
+
+    /// Sends a packet to GDB. Over a reliable connection GDB never asks for a
+    /// packet again, so its acknowledgements are not waited for.
+    fn send(&mut self, data: &str) -> Result<()> {
+        debug!("GDB< {data}");
+        write!(self.writer, "${data}#{:02x}", checksum(data.as_bytes()))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Formats a word as GDB expects registers, in target byte order.
+fn hex_word(word: &u32) -> String {
+    word.to_le_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Parses the `addr,len` arguments of memory and breakpoint packets.
+fn parse_pair(args: &str) -> Option<(u32, u32)> {
+    let (first, second) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(first, 16).ok()?,
+        u32::from_str_radix(second, 16).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Cursor, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use super::{checksum, GdbServer};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn packets(packets: &[&str]) -> Vec<u8> {
+        packets
+            .iter()
+            .map(|data| format!("${data}#{:02x}", checksum(data.as_bytes())))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn session() {
+        let input = packets(&[
+            "QStartNoAckMode",
+            "?",
+            "p20",
+            "m1000,6",
+            "m9000,4",
+            "Z0,1008,4",
+            "c",
+            "g",
+            "s",
+            "k",
+        ]);
+        let output = Output::default();
+        let mut server = GdbServer::new(Cursor::new(input), output.clone());
+
+        let mut regs = [0; 32];
+        regs[2] = 0x1234;
+        let memory = |addr: u32| (0x1000..0x1004).contains(&addr).then_some(addr as u8);
+        server.on_instruction(0x1000, &regs, &memory).unwrap();
+        server.on_instruction(0x1004, &regs, &memory).unwrap();
+        server.on_instruction(0x1008, &regs, &memory).unwrap();
+        assert!(server.on_instruction(0x100c, &regs, &memory).is_err());
+
+        let mut expected = vec![
+            "OK".to_string(),
+            "S05".to_string(),
+            "00100000".to_string(),
+            "00010203".to_string(),
+            "E14".to_string(),
+            "OK".to_string(),
+            "S05".to_string(),
+        ];
+        let mut all_regs = "00000000".repeat(33);
+        all_regs.replace_range(16..24, "34120000");
+        all_regs.replace_range(256..264, "08100000");
+        expected.push(all_regs);
+        expected.push("S05".to_string());
+        let expected: Vec<u8> = std::iter::once(b'+')
+            .chain(packets(
+                &expected.iter().map(String::as_str).collect::<Vec<_>>(),
+            ))
+            .collect();
+        assert_eq!(
+            String::from_utf8(output.0.lock().unwrap().clone()).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
+    }
+}
@@ -12,36 +12,106 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod emulator;
 pub mod exec;
+pub mod gdb;
+pub mod profiler;
+pub mod trace;
 
 use std::io::Write;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use lazy_static::lazy_static;
 use risc0_zkp::{
-    core::sha::default_implementation, hal::cpu::CpuHal, prove::adapter::ProveAdapter,
+    core::sha::default_implementation,
+    hal::cpu::CpuHal,
+    params::ProofParams,
+    prove::{adapter::ProveAdapter, executor::Executor, ProvePhase},
+    ZK_CYCLES,
 };
 use risc0_zkvm_circuit::CircuitImpl;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     elf::Program,
-    host::ProverOpts,
+    host::{
+        self, Backend, CancellationToken, Cancelled, ProgressCallback, ProverBackend, ProverError,
+        ProverOpts, ProverProgress,
+    },
     method_id::MethodId,
     platform::{
         io::{SENDRECV_CHANNEL_INITIAL_INPUT, SENDRECV_CHANNEL_STDERR, SENDRECV_CHANNEL_STDOUT},
         memory::MEM_SIZE,
+        WORD_SIZE,
     },
     receipt::Receipt,
 };
+use gdb::GdbServer;
+use trace::TraceSink;
 
 lazy_static! {
     pub static ref CIRCUIT: CircuitImpl = CircuitImpl::new();
 }
 
+/// How the guest finished, as reported by [Prover::execute].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExitStatus {
+    /// The guest ran to completion.
+    Halted,
+    /// The guest faulted, for example by panicking, with the given message.
+    Fault(String),
+}
+
+/// The execution trace of a guest, as returned by [Prover::trace]. It can be
+/// serialized, then proven later or on another machine with
+/// [Prover::prove_trace], without running the guest again. Proving the same
+/// trace twice gives receipts that only differ in their zero-knowledge
+/// randomness.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Trace {
+    journal: Vec<u32>,
+    exec: exec::SavedTrace,
+}
+
+impl Trace {
+    /// The words committed to the journal by the guest.
+    pub fn journal(&self) -> &[u32] {
+        &self.journal
+    }
+
+    /// The log2 of the number of cycles in the trace.
+    pub fn po2(&self) -> u32 {
+        self.exec.po2
+    }
+}
+
+/// A summary of a guest execution, as returned by [Prover::execute].
+#[derive(Clone, Debug)]
+pub struct SessionSummary {
+    /// The words committed to the journal.
+    pub journal: Vec<u32>,
+    /// The bytes written by the guest to stdout.
+    pub stdout: Vec<u8>,
+    /// The bytes written by the guest to stderr.
+    pub stderr: Vec<u8>,
+    /// The number of cycles executed, including loading the program.
+    pub cycles: usize,
+    /// The log2 of the trace size a proof of this execution needs.
+    pub po2: u32,
+    /// The number of bytes of memory touched by the guest, including its
+    /// loaded image.
+    pub memory_bytes: usize,
+    /// Whether the guest halted normally.
+    pub exit_status: ExitStatus,
+}
+
 pub struct Prover<'a> {
     elf: Program,
     inner: ProverImpl<'a>,
     method_id: MethodId,
+    monitor: Monitor<'a>,
+    trace_sink: Option<Box<dyn TraceSink + 'a + Send>>,
+    gdb_server: Option<GdbServer>,
 }
 
 impl<'a> Prover<'a> {
@@ -49,16 +119,35 @@ impl<'a> Prover<'a> {
         Self::new_with_opts(elf, method_id, ProverOpts::default())
     }
 
-    pub fn new_with_opts(elf: &[u8], method_id: &[u8], opts: ProverOpts<'a>) -> Result<Self> {
+    pub fn new_with_opts(elf: &[u8], method_id: &[u8], mut opts: ProverOpts<'a>) -> Result<Self> {
+        // Kept apart from the options so it can be used while the executor
+        // borrows them.
+        let monitor = Monitor {
+            progress_callback: opts.progress_callback.take(),
+            cancellation_token: opts.cancellation_token.take(),
+        };
+        let trace_sink = opts.trace_sink.take();
+        let gdb_server = opts.gdb_server.take();
+        // A guest stopped by the debugger is not proven.
+        if gdb_server.is_some() {
+            opts.skip_seal = true;
+        }
         Ok(Prover {
             elf: Program::load_elf(&elf, MEM_SIZE as u32)?,
             inner: ProverImpl::new(opts),
             method_id: MethodId::from_slice(method_id).unwrap(),
+            monitor,
+            trace_sink,
+            gdb_server,
         })
     }
 
+    /// Adds input for the guest, padded with zeros to a whole number of
+    /// words like any other input.
     pub fn add_input_u8_slice(&mut self, slice: &[u8]) {
         self.inner.input.extend_from_slice(slice);
+        let padding = (WORD_SIZE - slice.len() % WORD_SIZE) % WORD_SIZE;
+        self.inner.input.resize(self.inner.input.len() + padding, 0);
     }
 
     pub fn add_input_u32_slice(&mut self, slice: &[u32]) {
@@ -71,42 +160,242 @@ impl<'a> Prover<'a> {
         Ok(&self.inner.output)
     }
 
+    /// Runs the guest without proving it, returning a summary of the
+    /// execution. Unlike [ProverOpts::with_skip_seal], none of the structures
+    /// needed for a proof are built once execution finishes, and a guest fault
+    /// is reported in the summary rather than as an error.
+    ///
+    /// The guest runs on the [Emulator](emulator::Emulator) rather than the
+    /// circuit, unless a trace sink or a GDB server needs the instructions the
+    /// circuit decodes. Both give the same summary, apart from the
+    /// [memory_bytes](SessionSummary::memory_bytes) the circuit reads on its
+    /// own.
+    pub fn execute(&mut self) -> Result<SessionSummary> {
+        let monitor = &self.monitor;
+        let report = |cycles| monitor.report(ProvePhase::Execute, cycles);
+        let (result, cycles, po2, memory_bytes) = if self.can_emulate() {
+            let mut emulator = emulator::Emulator::new(&self.elf, &mut self.inner);
+            let result = emulator.run_with_monitor(report);
+            (
+                result,
+                emulator.cycles(),
+                emulator.po2(),
+                emulator.touched_bytes(),
+            )
+        } else {
+            let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
+            if let Some(sink) = &mut self.trace_sink {
+                executor = executor.with_trace_sink(sink.as_mut());
+            }
+            if let Some(server) = &mut self.gdb_server {
+                executor = executor.with_gdb_server(server);
+            }
+            let result = executor.execute_with_monitor(report);
+            (
+                result,
+                executor.executor.cycle,
+                executor.executor.po2,
+                executor.executor.custom.touched_bytes(),
+            )
+        };
+
+        let exit_status = match (result, self.inner.fault.take()) {
+            (_, Some(msg)) => ExitStatus::Fault(msg),
+            (Ok(()), None) => ExitStatus::Halted,
+            (Err(err), None) => return Err(err),
+        };
+        Ok(SessionSummary {
+            journal: self.inner.commit.clone(),
+            stdout: self.inner.output.clone(),
+            stderr: self.inner.stderr.clone(),
+            cycles,
+            po2: po2 as u32,
+            memory_bytes,
+            exit_status,
+        })
+    }
+
     pub fn run(&mut self) -> Result<Receipt> {
         let skip_seal = self.inner.opts.skip_seal;
+        let proof_params = self.inner.opts.proof_params;
+        let seed = self.inner.opts.seed;
+        check_proof_params(&proof_params)?;
 
-        let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
-        executor.run()?;
-
-        let mut prover = ProveAdapter::new(&mut executor.executor);
-        let hal = CpuHal::<CircuitImpl>::new(&CIRCUIT);
-        let sha = default_implementation();
+        let monitor = &self.monitor;
+        let report = |cycles| monitor.report(ProvePhase::Execute, cycles);
+        // Without a seal, nothing needs the trace the circuit builds.
+        if skip_seal && self.can_emulate() {
+            emulator::Emulator::new(&self.elf, &mut self.inner).run_with_monitor(report)?;
+            return self.make_receipt(self.inner.commit.clone(), Vec::new());
+        }
 
-        let seal = if skip_seal {
-            risc0_zkp::prove::prove_without_seal(&hal, sha, &mut prover);
-            Vec::new()
-        } else {
-            risc0_zkp::prove::prove(&hal, sha, &mut prover)
-        };
+        let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
+        if let Some(sink) = &mut self.trace_sink {
+            executor = executor.with_trace_sink(sink.as_mut());
+        }
+        if let Some(server) = &mut self.gdb_server {
+            executor = executor.with_gdb_server(server);
+        }
+        executor.executor.seed = seed;
+        executor.run_with_monitor(report)?;
+        let seal = prove_executor(&mut executor.executor, monitor, skip_seal, &proof_params)?;
 
         // Attach the full version of the output journal & construct receipt object
-        let receipt = Receipt {
+        self.make_receipt(self.inner.commit.clone(), seal)
+    }
+
+    /// Runs the guest and returns its execution [Trace], to be proven later
+    /// with [Prover::prove_trace].
+    pub fn trace(&mut self) -> Result<Trace> {
+        let monitor = &self.monitor;
+        let mut executor = exec::RV32Executor::new(&CIRCUIT, &self.elf, &mut self.inner);
+        if let Some(sink) = &mut self.trace_sink {
+            executor = executor.with_trace_sink(sink.as_mut());
+        }
+        if let Some(server) = &mut self.gdb_server {
+            executor = executor.with_gdb_server(server);
+        }
+        executor.execute_with_monitor(|cycles| monitor.report(ProvePhase::Execute, cycles))?;
+        let exec = executor.save_trace();
+        Ok(Trace {
             journal: self.inner.commit.clone(),
-            seal,
-        };
+            exec,
+        })
+    }
 
-        if !skip_seal {
+    /// Proves a [Trace] saved by [Prover::trace], possibly on another machine,
+    /// without running the guest again. Only the options and the method ID of
+    /// this prover are used.
+    pub fn prove_trace(&mut self, trace: &Trace) -> Result<Receipt> {
+        let skip_seal = self.inner.opts.skip_seal;
+        let proof_params = self.inner.opts.proof_params;
+        let seed = self.inner.opts.seed;
+        check_proof_params(&proof_params)?;
+
+        let mut executor = exec::load_trace(&CIRCUIT, &mut self.inner, &trace.exec, seed)?;
+        let seal = prove_executor(&mut executor, &self.monitor, skip_seal, &proof_params)?;
+        self.make_receipt(trace.journal.clone(), seal)
+    }
+
+    /// Whether the guest can run on the [Emulator](emulator::Emulator), since
+    /// no trace sink or GDB server needs the instructions the circuit decodes.
+    fn can_emulate(&self) -> bool {
+        self.trace_sink.is_none() && self.gdb_server.is_none()
+    }
+
+    fn make_receipt(&self, journal: Vec<u32>, seal: Vec<u32>) -> Result<Receipt> {
+        let receipt = Receipt { journal, seal };
+
+        if !self.inner.opts.skip_seal {
             // Verify receipt to make sure it works
-            receipt.verify(&self.method_id)?;
+            let min_security_bits = self.inner.opts.proof_params.security_bits();
+            receipt.verify_with_min_security(&self.method_id, min_security_bits)?;
         }
 
         Ok(receipt)
     }
 }
 
+impl<'a> ProverBackend for Prover<'a> {
+    fn backend(&self) -> Backend {
+        Backend::Rust
+    }
+
+    fn add_input_u8_slice(&mut self, slice: &[u8]) -> Result<()> {
+        Prover::add_input_u8_slice(self, slice);
+        Ok(())
+    }
+
+    fn add_input_u32_slice(&mut self, slice: &[u32]) -> Result<()> {
+        Prover::add_input_u32_slice(self, slice);
+        Ok(())
+    }
+
+    fn get_output(&self) -> Result<&[u8]> {
+        Prover::get_output(self)
+    }
+
+    fn run(&mut self) -> Result<host::Receipt> {
+        Ok(Prover::run(self)?.into())
+    }
+
+    fn execute(&mut self) -> Result<SessionSummary> {
+        Prover::execute(self)
+    }
+
+    fn trace(&mut self) -> Result<Trace> {
+        Prover::trace(self)
+    }
+
+    fn prove_trace(&mut self, trace: &Trace) -> Result<host::Receipt> {
+        Ok(Prover::prove_trace(self, trace)?.into())
+    }
+}
+
+/// Checks that the prover can use the [ProofParams] from the [ProverOpts],
+/// before any time is spent running the guest.
+fn check_proof_params(params: &ProofParams) -> Result<()> {
+    if !params.is_valid() {
+        bail!("Invalid proof parameters: {params:?}");
+    }
+    if params.queries > ZK_CYCLES {
+        bail!(
+            "Proof parameters use {} queries, at most {ZK_CYCLES} are supported",
+            params.queries
+        );
+    }
+    Ok(())
+}
+
+/// Proves a finalized execution trace, returning the seal.
+fn prove_executor<H: exec::IoHandler>(
+    executor: &mut Executor<CircuitImpl, exec::MachineContext<H>>,
+    monitor: &Monitor,
+    skip_seal: bool,
+    proof_params: &ProofParams,
+) -> Result<Vec<u32>> {
+    let cycles = executor.cycle;
+    let mut prover = ProveAdapter::new(executor);
+    let hal = CpuHal::<CircuitImpl>::new(&CIRCUIT);
+    let sha = default_implementation();
+
+    if skip_seal {
+        risc0_zkp::prove::prove_without_seal(&hal, sha, &mut prover);
+        return Ok(Vec::new());
+    }
+    let seal =
+        risc0_zkp::prove::prove_with_monitor(&hal, sha, &mut prover, proof_params, |phase| {
+            monitor.report(phase, cycles).is_ok()
+        })
+        .map_err(|_| Cancelled)?;
+    Ok(seal)
+}
+
+/// Reports progress to the callback from the [ProverOpts] and checks its
+/// [CancellationToken].
+struct Monitor<'a> {
+    progress_callback: Option<ProgressCallback<'a>>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+impl<'a> Monitor<'a> {
+    fn report(&self, phase: ProvePhase, cycles: usize) -> Result<()> {
+        if let Some(callback) = &self.progress_callback {
+            callback(&ProverProgress { phase, cycles });
+        }
+        match &self.cancellation_token {
+            Some(token) if token.is_cancelled() => Err(Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
 struct ProverImpl<'a> {
     pub input: Vec<u8>,
     pub output: Vec<u8>,
+    pub stderr: Vec<u8>,
     pub commit: Vec<u32>,
+    pub fault: Option<String>,
     pub opts: ProverOpts<'a>,
 }
 
@@ -115,33 +404,38 @@ impl<'a> ProverImpl<'a> {
         Self {
             input: Vec::new(),
             output: Vec::new(),
+            stderr: Vec::new(),
             commit: Vec::new(),
+            fault: None,
             opts,
         }
     }
 }
 
 impl<'a> exec::IoHandler for ProverImpl<'a> {
-    fn on_txrx(&mut self, channel: u32, buf: &[u8]) -> Vec<u8> {
-        if let Some(cb) = self.opts.sendrecv_callbacks.get(&channel) {
-            return cb(channel, buf);
+    fn on_txrx(&mut self, channel: u32, cycle: u32, buf: &[u8]) -> Result<Vec<u8>, ProverError> {
+        if let Some(handler) = self.opts.sendrecv_handlers.get_mut(&channel) {
+            return ProverError::catch_callback(channel, || {
+                handler.handle(channel, Some(cycle), buf)
+            });
         }
         match channel {
             SENDRECV_CHANNEL_INITIAL_INPUT => {
                 log::debug!("SENDRECV_CHANNEL_INITIAL_INPUT: {}", buf.len());
-                self.input.clone()
+                Ok(self.input.clone())
             }
             SENDRECV_CHANNEL_STDOUT => {
                 log::debug!("SENDRECV_CHANNEL_STDOUT: {}", buf.len());
                 self.output.extend(buf);
-                Vec::new()
+                Ok(Vec::new())
             }
             SENDRECV_CHANNEL_STDERR => {
                 log::debug!("SENDRECV_CHANNEL_STDERR: {}", buf.len());
                 std::io::stderr().lock().write_all(buf).unwrap();
-                Vec::new()
+                self.stderr.extend(buf);
+                Ok(Vec::new())
             }
-            _ => panic!("Unknown channel: {channel}"),
+            _ => Err(ProverError::UnknownChannel(channel)),
         }
     }
 
@@ -150,6 +444,6 @@ impl<'a> exec::IoHandler for ProverImpl<'a> {
     }
 
     fn on_fault(&mut self, msg: &str) {
-        panic!("{}", msg);
+        self.fault = Some(msg.to_string());
     }
 }
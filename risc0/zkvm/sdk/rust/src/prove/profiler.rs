@@ -0,0 +1,233 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Write},
+};
+
+use anyhow::Result;
+
+use super::trace::{TraceEvent, TraceSink};
+use crate::elf::{self, Symbol};
+
+/// The RISC-V link registers, which calls write the return address to.
+const LINK_REGISTERS: [u32; 2] = [1, 5];
+
+/// A [TraceSink] that profiles where a guest spends its cycles.
+///
+/// Each instruction is charged the cycles until the next instruction is
+/// decoded, and the cycles are attributed to the call stack the instruction
+/// ran in. Call stacks are rebuilt from the jumps the guest makes: a jump that
+/// writes a link register calls the function it lands in, and a `jalr` through
+/// `ra` returns. Program counters are mapped to the function symbols of the
+/// guest's ELF file, so a stripped file only gives `[unknown]` frames.
+pub struct Profiler {
+    symbols: Vec<Symbol>,
+    // The frames of the current call stack, as indexes into `symbols`.
+    stack: Vec<Option<usize>>,
+    // The instruction currently running, as (cycle, insn, kind).
+    current: Option<(u32, u32, &'static str)>,
+    end_cycle: u32,
+    cycles: HashMap<Vec<Option<usize>>, u64>,
+}
+
+impl Profiler {
+    /// Creates a profiler for the guest in the given ELF file.
+    pub fn new(elf: &[u8]) -> Result<Self> {
+        Ok(Self::from_symbols(elf::load_symbols(elf)?))
+    }
+
+    /// Creates a profiler that maps program counters to the given function
+    /// symbols.
+    pub fn from_symbols(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|symbol| symbol.addr);
+        Self {
+            symbols,
+            stack: Vec::new(),
+            current: None,
+            end_cycle: 0,
+            cycles: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of cycles spent in each call stack, keyed by the
+    /// function names of the stack from the outermost call, separated by `;`.
+    pub fn folded_stacks(&self) -> BTreeMap<String, u64> {
+        let mut stacks = BTreeMap::new();
+        let mut add = |stack: &[Option<usize>], cycles: u64| {
+            let names: Vec<&str> = stack.iter().map(|frame| self.name(*frame)).collect();
+            *stacks.entry(names.join(";")).or_insert(0) += cycles;
+        };
+        for (stack, cycles) in self.cycles.iter() {
+            add(stack, *cycles);
+        }
+        if let Some((cycle, _, _)) = self.current {
+            add(&self.stack, (self.end_cycle - cycle) as u64);
+        }
+        stacks
+    }
+
+    /// Writes the profile in the folded stack format read by flamegraph
+    /// tools, such as `inferno-flamegraph` or `flamegraph.pl`: one line per
+    /// call stack, followed by its number of cycles. Function names are
+    /// written as they appear in the symbol table, which can be demangled with
+    /// `rustfilt`.
+    pub fn write_folded(&self, mut out: impl Write) -> io::Result<()> {
+        for (stack, cycles) in self.folded_stacks() {
+            writeln!(out, "{stack} {cycles}")?;
+        }
+        Ok(())
+    }
+
+    fn name(&self, frame: Option<usize>) -> &str {
+        match frame {
+            Some(idx) => &self.symbols[idx].name,
+            None => "[unknown]",
+        }
+    }
+
+    fn lookup(&self, pc: u32) -> Option<usize> {
+        let idx = self
+            .symbols
+            .partition_point(|symbol| symbol.addr <= pc)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[idx];
+        (symbol.size == 0 || pc - symbol.addr < symbol.size).then_some(idx)
+    }
+
+    fn on_instruction(&mut self, cycle: u32, pc: u32, insn: u32, kind: &'static str) {
+        let frame = self.lookup(pc);
+        if let Some((start, prev_insn, prev_kind)) = self.current {
+            let cycles = (cycle - start) as u64;
+            match self.cycles.get_mut(self.stack.as_slice()) {
+                Some(total) => *total += cycles,
+                None => {
+                    self.cycles.insert(self.stack.clone(), cycles);
+                }
+            }
+
+            let rd = (prev_insn >> 7) & 0x1f;
+            let rs1 = (prev_insn >> 15) & 0x1f;
+            let is_jump = prev_kind == "JAL" || prev_kind == "JALR";
+            if is_jump && LINK_REGISTERS.contains(&rd) {
+                self.stack.push(frame);
+            } else if prev_kind == "JALR" && rd == 0 && rs1 == 1 && self.stack.len() > 1 {
+                self.stack.pop();
+            }
+        }
+        // Anything else that leaves the function, such as a tail call, or a
+        // return to a caller that was never seen, replaces the current frame.
+        match self.stack.last_mut() {
+            Some(top) => *top = frame,
+            None => self.stack.push(frame),
+        }
+        self.current = Some((cycle, insn, kind));
+        self.end_cycle = cycle + 1;
+    }
+}
+
+impl TraceSink for Profiler {
+    fn on_event(&mut self, event: &TraceEvent) {
+        match *event {
+            TraceEvent::Instruction {
+                cycle,
+                pc,
+                insn,
+                kind,
+            } => self.on_instruction(cycle, pc, insn, kind),
+            TraceEvent::MemoryRead { cycle, .. } | TraceEvent::MemoryWrite { cycle, .. } => {
+                if self.current.is_some() {
+                    self.end_cycle = self.end_cycle.max(cycle + 1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Profiler, TraceEvent, TraceSink};
+    use crate::elf::Symbol;
+
+    const RA: u32 = 1;
+
+    fn symbol(name: &str, addr: u32, size: u32) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            addr,
+            size,
+        }
+    }
+
+    // Encodes the fields of a jump that the profiler looks at.
+    fn jump(rd: u32, rs1: u32) -> u32 {
+        rs1 << 15 | rd << 7
+    }
+
+    fn run(profiler: &mut Profiler, insns: &[(u32, u32, u32, &'static str)]) {
+        for (cycle, pc, insn, kind) in insns.iter().copied() {
+            profiler.on_event(&TraceEvent::Instruction {
+                cycle,
+                pc,
+                insn,
+                kind,
+            });
+        }
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let mut profiler = Profiler::from_symbols(vec![
+            symbol("main", 0x1000, 0x100),
+            symbol("helper", 0x2000, 0x100),
+            symbol("leaf", 0x3000, 0x100),
+        ]);
+        run(
+            &mut profiler,
+            &[
+                (10, 0x1000, 0, "ADDI"),
+                (12, 0x1004, jump(RA, 0), "JAL"),
+                (14, 0x2000, 0, "MUL"),
+                (20, 0x2004, jump(0, 0), "JAL"),
+                (22, 0x3000, jump(0, RA), "JALR"),
+                (24, 0x1008, 0, "ADDI"),
+                (26, 0x5000, 0, "ADDI"),
+            ],
+        );
+        profiler.on_event(&TraceEvent::MemoryWrite {
+            cycle: 29,
+            addr: 0x4000,
+            value: 0,
+        });
+
+        let stacks: Vec<_> = profiler.folded_stacks().into_iter().collect();
+        assert_eq!(
+            stacks,
+            vec![
+                ("[unknown]".to_string(), 4),
+                ("main".to_string(), 6),
+                ("main;helper".to_string(), 8),
+                ("main;leaf".to_string(), 2),
+            ]
+        );
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "[unknown] 4\nmain 6\nmain;helper 8\nmain;leaf 2\n"
+        );
+    }
+}
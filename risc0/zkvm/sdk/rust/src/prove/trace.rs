@@ -0,0 +1,102 @@
+// Copyright 2022 Risc0, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The mnemonics of the instructions the circuit decodes. ECALL is decoded as
+/// HALT.
+const MNEMONICS: &[&str] = &[
+    "ADD", "SUB", "XOR", "OR", "AND", "SLT", "SLTU", "ADDI", "XORI", "ORI", "ANDI", "SLTI",
+    "SLTIU", "LB", "LH", "LW", "LBU", "LHU", "SB", "SH", "SW", "BEQ", "BNE", "BLT", "BGE", "BLTU",
+    "BGEU", "JAL", "JALR", "LUI", "AUIPC", "MUL", "MULH", "MULSU", "MULU", "SLL", "SLLI", "DIV",
+    "DIVU", "REM", "REMU", "SRL", "SRA", "SRLI", "SRAI", "HALT",
+];
+
+/// An event in the execution of a guest, as reported to a [TraceSink].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// The guest decoded an instruction, which runs until the next one is
+    /// decoded.
+    Instruction {
+        /// The cycle the instruction was decoded in.
+        cycle: u32,
+        /// The byte address of the instruction.
+        pc: u32,
+        /// The instruction word.
+        insn: u32,
+        /// The mnemonic of the instruction, as decoded by the circuit.
+        kind: &'static str,
+    },
+    /// A word of memory was read, including the fetch of an instruction.
+    MemoryRead {
+        /// The cycle of the read.
+        cycle: u32,
+        /// The byte address of the word.
+        addr: u32,
+        /// The word read.
+        value: u32,
+    },
+    /// A word of memory was written, including while the program is loaded.
+    MemoryWrite {
+        /// The cycle of the write.
+        cycle: u32,
+        /// The byte address of the word.
+        addr: u32,
+        /// The word written.
+        value: u32,
+    },
+}
+
+/// Receives the [TraceEvent]s of a guest as it runs. Tracing is opt-in and
+/// only supported by the Rust prover, see
+/// [ProverOpts::with_trace_sink](crate::host::ProverOpts::with_trace_sink).
+pub trait TraceSink {
+    /// Called with each event, in the order they happen.
+    fn on_event(&mut self, event: &TraceEvent);
+}
+
+impl<S: TraceSink + ?Sized> TraceSink for &mut S {
+    fn on_event(&mut self, event: &TraceEvent) {
+        (**self).on_event(event)
+    }
+}
+
+/// Collects every event, which takes a lot of memory for long executions.
+impl TraceSink for Vec<TraceEvent> {
+    fn on_event(&mut self, event: &TraceEvent) {
+        self.push(*event);
+    }
+}
+
+/// Returns the mnemonic of the instruction decoded by a cycle that logged
+/// `msg`, if it is a decode message.
+pub(crate) fn decoded_mnemonic(msg: &str) -> Option<&'static str> {
+    let (_, rest) = msg.split_once(" Decode: ")?;
+    let name = rest.split(' ').next()?;
+    MNEMONICS.iter().copied().find(|mnemonic| *mnemonic == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::decoded_mnemonic;
+
+    #[test]
+    fn decode_message() {
+        let msg = "C%u: pc: %08x Decode: ADDI r%u=0x%04x%04x, r%u=0x%04x%04x, imm=0x%04x%04x";
+        assert_eq!(decoded_mnemonic(msg), Some("ADDI"));
+        assert_eq!(
+            decoded_mnemonic("C%u: pc: %08x Final: 0x%04x%04x -> r%u"),
+            None
+        );
+        assert_eq!(decoded_mnemonic("C%u: pc: %08x Decode: NOPE r%u"), None);
+    }
+}
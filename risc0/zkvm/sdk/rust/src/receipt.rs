@@ -12,14 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use anyhow::{anyhow, Result};
+use alloc::vec::Vec;
+
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
 
-use risc0_zkp::core::sha::default_implementation;
-use risc0_zkp::verify::adapter::VerifyAdapter;
+use risc0_zkp::core::{
+    fp::Fp,
+    sha::{default_implementation, Digest, Sha, DIGEST_WORDS},
+};
+use risc0_zkp::{
+    adapter::TapsProvider,
+    params::ProofParams,
+    verify::{adapter::VerifyAdapter, SealSizes, VerifyCache},
+};
 use risc0_zkvm_circuit::CircuitImpl;
 
-use crate::method_id::MethodId;
+use crate::{
+    method_id::{MethodDigest, MethodId},
+    platform::WORD_SIZE,
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct Receipt {
@@ -27,38 +39,229 @@ pub struct Receipt {
     pub seal: Vec<u32>,
 }
 
-// FIXME: Remove this temporary trait to convert method IDs once our API is the
-// same between FFI and rust-based provers.
-pub trait IntoMethodId {}
-impl IntoMethodId for &MethodId {}
-impl IntoMethodId for &[u8] {}
+/// The statement committed by a guest that verified another [Receipt] inside
+/// the zkVM: the digest of the inner method ID and the digest of the inner
+/// journal.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReceiptClaim {
+    /// The SHA-256 digest of the inner method ID, in the
+    /// [MethodId::as_slice] format.
+    pub method_id: Digest,
+    /// The SHA-256 digest of the inner journal bytes.
+    pub journal: Digest,
+}
+
+impl ReceiptClaim {
+    /// Computes the claim for a receipt of the method with the given ID,
+    /// hashing with `sha` all the bytes of the method ID table, as returned by
+    /// [MethodId::as_slice], and all the bytes of the journal, as returned by
+    /// [Receipt::get_journal].
+    pub fn new<S: Sha>(sha: &S, method_id: &[u8], journal: &[u8]) -> Self {
+        ReceiptClaim {
+            method_id: *sha.hash_bytes(method_id),
+            journal: *sha.hash_bytes(journal),
+        }
+    }
+}
+
+/// A summary of a [Receipt]'s seal, as returned by [Receipt::inspect].
+#[derive(Clone, Debug)]
+pub struct ReceiptInfo {
+    /// The size of the execution trace as a power of 2.
+    pub po2: u32,
+    /// The number of cycles in the execution trace, including padding.
+    pub cycles: usize,
+    /// The parameters the seal was produced with.
+    pub params: ProofParams,
+    /// The circuit outputs: the journal root followed by the journal length in
+    /// bytes.
+    pub outputs: Vec<u32>,
+    pub code_root: Digest,
+    pub data_root: Digest,
+    pub accum_root: Digest,
+    pub check_root: Digest,
+    /// The number of words in each section of the seal.
+    pub sizes: SealSizes,
+}
+
+/// A method ID in any of the forms it is passed around in: a parsed
+/// [MethodId], or the bytes of [MethodId::as_slice].
+pub trait IntoMethodId {
+    fn into_method_id(self) -> Result<MethodId>;
+}
+
+impl IntoMethodId for &MethodId {
+    fn into_method_id(self) -> Result<MethodId> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoMethodId for &[u8] {
+    fn into_method_id(self) -> Result<MethodId> {
+        MethodId::from_slice(self)
+    }
+}
 
 impl Receipt {
     #[cfg(feature = "verify")]
-    pub fn verify<M>(&self, _method_id: M) -> Result<()>
+    pub fn verify<M>(&self, method_id: M) -> Result<()>
+    where
+        M: IntoMethodId,
+    {
+        self.verify_with_hash(default_implementation(), method_id)
+    }
+
+    /// Verifies the receipt against a [MethodDigest], taking the code roots
+    /// from `table`, a [MethodId] in the [MethodId::as_slice] format, once it
+    /// has been checked against the digest.
+    #[cfg(feature = "verify")]
+    pub fn verify_with_digest(&self, digest: &MethodDigest, table: &[u8]) -> Result<()> {
+        self.verify(&MethodId::from_digest(digest, table)?)
+    }
+
+    /// Verifies the receipt using the given [Sha] implementation, such as the
+    /// accelerated one available to guests.
+    #[cfg(feature = "verify")]
+    pub fn verify_with_hash<S, M>(&self, sha: &S, method_id: M) -> Result<()>
+    where
+        S: Sha,
+        M: IntoMethodId,
+    {
+        let method_id = method_id.into_method_id()?;
+        let min_security_bits = ProofParams::DEFAULT.security_bits();
+        let circuit = CircuitImpl::new();
+        self.verify_with_code_roots(
+            sha,
+            &circuit,
+            &VerifyCache::new(circuit.get_taps()),
+            &method_id.table,
+            min_security_bits,
+        )
+    }
+
+    /// Verifies the receipt, accepting seals produced with [ProofParams] that
+    /// provide at least `min_security_bits` of conjectured security, rather
+    /// than that of the default parameters.
+    #[cfg(feature = "verify")]
+    pub fn verify_with_min_security<M>(&self, method_id: M, min_security_bits: usize) -> Result<()>
     where
         M: IntoMethodId,
     {
+        let method_id = method_id.into_method_id()?;
         let circuit = CircuitImpl::new();
-        let mut verifier = VerifyAdapter::new(&circuit);
-        let sha = default_implementation();
-        risc0_zkp::verify::verify(sha, &mut verifier, &self.seal)
-            .map_err(|err| anyhow!("Verification failed: {:?}", err))
+        self.verify_with_code_roots(
+            default_implementation(),
+            &circuit,
+            &VerifyCache::new(circuit.get_taps()),
+            &method_id.table,
+            min_security_bits,
+        )
+    }
+
+    /// Verifies the receipt against the code roots of an already parsed
+    /// [MethodId], using the tap and FRI setup in `cache`.
+    #[cfg(feature = "verify")]
+    pub(crate) fn verify_with_code_roots<S: Sha>(
+        &self,
+        sha: &S,
+        circuit: &CircuitImpl,
+        cache: &VerifyCache,
+        code_roots: &[Digest],
+        min_security_bits: usize,
+    ) -> Result<()> {
+        let mut verifier = VerifyAdapter::new(circuit, code_roots);
+        risc0_zkp::verify::verify_with_cache(
+            sha,
+            &mut verifier,
+            cache,
+            &self.seal,
+            min_security_bits,
+        )
+        .map_err(|err| anyhow!("Verification failed: {:?}", err))?;
+
+        let output = merge_outputs(verifier.out());
+        let journal_len = (self.journal.len() * WORD_SIZE) as u32;
+        if output[DIGEST_WORDS] != journal_len {
+            bail!(
+                "Receipt::verify> journal size ({}) does not match receipt seal ({})",
+                journal_len,
+                output[DIGEST_WORDS]
+            );
+        }
+        if output[..DIGEST_WORDS] != journal_root(sha, &self.journal) {
+            bail!("Receipt journal/seal root mismatch");
+        }
+        Ok(())
     }
 
-    // Compatible API with FFI-based prover.
+    /// Returns the size and layout of the seal without verifying it.
+    #[cfg(feature = "verify")]
+    pub fn inspect(&self) -> Result<ReceiptInfo> {
+        let circuit = CircuitImpl::new();
+        let mut adapter = VerifyAdapter::new(&circuit, &[]);
+        let info = risc0_zkp::verify::inspect(default_implementation(), &mut adapter, &self.seal)
+            .map_err(|err| anyhow!("Malformed seal: {}", err))?;
+        Ok(ReceiptInfo {
+            po2: info.po2,
+            cycles: 1 << info.po2,
+            params: info.params,
+            outputs: merge_outputs(adapter.out()),
+            code_root: info.code_root,
+            data_root: info.data_root,
+            accum_root: info.accum_root,
+            check_root: info.check_root,
+            sizes: info.sizes,
+        })
+    }
+
+    /// Constructs a [Receipt] from a journal of little-endian words and a
+    /// seal.
+    pub fn new(journal: &[u8], seal: &[u32]) -> Result<Self> {
+        let chunks = journal.chunks_exact(WORD_SIZE);
+        if chunks.remainder().len() != 0 {
+            bail!("Journal must be a whole number of words");
+        }
+        Ok(Receipt {
+            journal: chunks
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+            seal: seal.to_vec(),
+        })
+    }
+
+    /// Returns a copy of the journal words.
     pub fn get_journal_vec(&self) -> Result<Vec<u32>> {
         Ok(self.journal.clone())
     }
 
-    // Compatible API with FFI-based prover.
+    /// Returns the journal words as bytes, in native byte order. This is
+    /// little-endian on the zkVM and on every host it supports.
     pub fn get_journal(&self) -> Result<&[u8]> {
         Ok(bytemuck::cast_slice(self.journal.as_slice()))
     }
 
-    // Compatible API with FFI-based prover.
-    // FIXME: Change API to avoid copy.
     pub fn get_seal(&self) -> Result<&[u32]> {
         Ok(self.seal.as_slice())
     }
 }
+
+/// Merges the circuit outputs, where each output register is split into low and
+/// high 16-bit halves, into words.
+fn merge_outputs(out: &[Fp]) -> Vec<u32> {
+    out.chunks_exact(2)
+        .map(|half| u32::from(half[0]) | u32::from(half[1]) << 16)
+        .collect()
+}
+
+/// Computes the value the guest places in its result registers for the given
+/// journal, as done by `risc0_zkvm_guest::env::finalize`: the journal itself
+/// padded with zeros if it is 32 bytes or less, otherwise its SHA-256 digest.
+fn journal_root<S: Sha>(sha: &S, journal: &[u32]) -> [u32; DIGEST_WORDS] {
+    let mut root = [0; DIGEST_WORDS];
+    if journal.len() <= DIGEST_WORDS {
+        root[..journal.len()].copy_from_slice(journal);
+    } else {
+        root.copy_from_slice(sha.hash_words(journal).as_slice());
+    }
+    root
+}
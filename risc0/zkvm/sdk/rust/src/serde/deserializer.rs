@@ -125,6 +125,26 @@ impl<'de> Deserializer<'de> {
         Deserializer { slice }
     }
 
+    /// Deserializes the next value, leaving any words after it to be read by
+    /// later calls. Values may borrow from the underlying slice.
+    pub fn read<T: Deserialize<'de>>(&mut self) -> Result<T> {
+        T::deserialize(&mut *self)
+    }
+
+    /// Returns the number of words that have not been deserialized yet.
+    pub fn remaining(&self) -> usize {
+        self.slice.len()
+    }
+
+    /// Checks that every word has been deserialized.
+    pub fn finish(&self) -> Result<()> {
+        if self.slice.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DeserializeTrailingData)
+        }
+    }
+
     fn try_take_word(&mut self) -> Result<u32> {
         if self.slice.len() >= 1 {
             let (head, tail) = self.slice.split_first().unwrap();
@@ -522,4 +542,21 @@ mod test {
         };
         assert_eq!(expected, from_slice(&words).unwrap());
     }
+
+    #[test]
+    fn test_read_sequence() {
+        let mut words = crate::serde::to_vec(&"abc").unwrap();
+        words.extend(crate::serde::to_vec(&(7u32, 8u64)).unwrap());
+        words.push(9);
+
+        let mut de = Deserializer::new(&words);
+        let first: &str = de.read().unwrap();
+        assert_eq!(first, "abc");
+        assert_eq!(de.read::<(u32, u64)>().unwrap(), (7, 8));
+        assert_eq!(de.remaining(), 1);
+        assert_eq!(de.finish(), Err(Error::DeserializeTrailingData));
+        assert_eq!(de.read::<u32>().unwrap(), 9);
+        assert_eq!(de.finish(), Ok(()));
+        assert_eq!(de.read::<u32>(), Err(Error::DeserializeUnexpectedEnd));
+    }
 }
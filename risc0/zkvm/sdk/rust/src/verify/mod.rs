@@ -11,3 +11,81 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use alloc::vec::Vec;
+
+use anyhow::Result;
+use risc0_zkp::{
+    adapter::TapsProvider, core::sha::default_implementation, params::ProofParams,
+    verify::VerifyCache,
+};
+use risc0_zkvm_circuit::CircuitImpl;
+
+use crate::{
+    method_id::MethodId,
+    receipt::{IntoMethodId, Receipt},
+};
+
+/// Verifies many receipts against the same method ID.
+///
+/// The method ID is parsed, and the circuit's taps and the FRI layout of
+/// seals using the default [ProofParams] are set up once, rather than on every
+/// call to [Receipt::verify].
+pub struct BatchVerifier {
+    circuit: CircuitImpl,
+    cache: VerifyCache,
+    method_id: MethodId,
+    min_security_bits: usize,
+}
+
+impl BatchVerifier {
+    pub fn new<M: IntoMethodId>(method_id: M) -> Result<Self> {
+        let circuit = CircuitImpl::new();
+        let cache = VerifyCache::new(circuit.get_taps()).with_params(ProofParams::DEFAULT);
+        Ok(BatchVerifier {
+            circuit,
+            cache,
+            method_id: method_id.into_method_id()?,
+            min_security_bits: ProofParams::DEFAULT.security_bits(),
+        })
+    }
+
+    /// Accepts receipts with at least `min_security_bits` of conjectured
+    /// security, as with [Receipt::verify_with_min_security].
+    pub fn with_min_security_bits(self, min_security_bits: usize) -> Self {
+        Self {
+            min_security_bits,
+            ..self
+        }
+    }
+
+    /// Verifies a single receipt.
+    pub fn verify(&self, receipt: &Receipt) -> Result<()> {
+        receipt.verify_with_code_roots(
+            default_implementation(),
+            &self.circuit,
+            &self.cache,
+            &self.method_id.table,
+            self.min_security_bits,
+        )
+    }
+
+    /// Verifies each of the given receipts, returning their results in the
+    /// same order. With the `parallel` feature enabled, receipts are verified
+    /// concurrently on the rayon thread pool.
+    pub fn verify_all(&self, receipts: &[Receipt]) -> Vec<Result<()>> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            receipts
+                .par_iter()
+                .map(|receipt| self.verify(receipt))
+                .collect()
+        }
+        #[cfg(not(feature = "parallel"))]
+        receipts
+            .iter()
+            .map(|receipt| self.verify(receipt))
+            .collect()
+    }
+}